@@ -1,4 +1,5 @@
 use clap::{Args, Parser, Subcommand};
+use verifier_core::checks::OutputArgs;
 
 #[derive(Subcommand, Debug)]
 pub enum ProverEntity {
@@ -14,11 +15,16 @@ pub struct ProveArgs {
     pub memory_bin_path: String,
     pub proof_path: String,
     pub output_path: String,
+    /// `output_path` holds the outputs of several transactions, see `hyle_contract::batch`.
+    #[arg(long)]
+    pub batch: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct VerifyArgs {
     pub proof_path: String,
+    #[command(flatten)]
+    pub output_args: OutputArgs,
 }
 #[derive(Parser, Debug)]
 pub struct ProverArgs {
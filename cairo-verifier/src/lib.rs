@@ -2,6 +2,7 @@ use utils::{prove, error::VerifierError};
 use wasm_bindgen::prelude::*;
 
 pub mod utils;
+pub mod verifier;
 
 
 extern crate web_sys;
@@ -1,42 +1,70 @@
 use std::fs;
+use std::path::Path;
 
 use clap::Parser;
+use verifier_core::envelope::{Phase, Recorder};
+use verifier_core::{exit_with, ProofOutputs, Verifier, VerifyError};
 use crate::utils::error::VerifierError;
+use crate::verifier::{CairoProof, CairoVerifier};
 
 mod commands;
 mod utils;
+mod verifier;
 
 #[derive(Parser)]
 struct Cli {
     proof_path: String,
 }
 
-fn main() -> Result<(), VerifierError> {
-    let args: commands::ProverArgs = commands::ProverArgs::parse();
+fn verify(args: &commands::VerifyArgs, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    let (checks, proof) = recorder.time(Phase::Decode, || -> Result<_, VerifyError> {
+        let checks = args.output_args.checks()?;
+        let proof = CairoProof::read(Path::new(&args.proof_path))?;
+        Ok((checks, proof))
+    })?;
 
-    let res = match args.entity {
-        commands::ProverEntity::Verify(args) => {   
-            utils::verify_proof(&args.proof_path)
-        },
-        commands::ProverEntity::Prove(args) => {
-            let program_output_str: String = fs::read_to_string(&args.output_path).expect("Failed to read output file");
-
-            let trace_data = fs::read(&args.trace_bin_path).expect("failed to load trace file");
-            let memory_data = fs::read(&args.memory_bin_path).expect("failed to load memory file");
-            let proof = utils::prove(
-                trace_data,
-                memory_data,
-                &program_output_str
-            )?;
-            std::fs::write(&args.proof_path, proof)?;
-            Ok(format!("Proof written to {}", &args.proof_path))
-        }
+    let outputs = recorder.time(Phase::Verify, || CairoVerifier.verify_outputs(&(), &proof))?;
+    checks.run_recorded(&outputs, recorder)?;
+    Ok(outputs)
+}
+
+fn prove(args: commands::ProveArgs) -> Result<String, VerifyError> {
+    let read = |path: &String| {
+        fs::read(path).map_err(|err| VerifyError::Decode(format!("failed to read {}: {}", path, err)))
+    };
+    let read_to_string = |path: &String| {
+        fs::read_to_string(path).map_err(|err| VerifyError::Decode(format!("failed to read {}: {}", path, err)))
     };
-    match res {
-        Result::Ok(output) => println!("{}", output),
-        Result::Err(err) => {
-            return Err(err);
-        }
+    let prove_error = |err: VerifierError| VerifyError::Internal(err.0);
+    let trace_data = read(&args.trace_bin_path)?;
+    let memory_data = read(&args.memory_bin_path)?;
+    let proof = if args.batch {
+        let program_output_str = read_to_string(&args.output_path)?;
+        utils::prove_batch(trace_data, memory_data, &program_output_str).map_err(prove_error)?
+    } else {
+        let program_output_str = read_to_string(&args.output_path)?;
+        utils::prove(
+            trace_data,
+            memory_data,
+            &program_output_str
+        ).map_err(prove_error)?
     };
-    Ok(())
+    std::fs::write(&args.proof_path, proof)
+        .map_err(|err| VerifyError::Internal(format!("failed to write {}: {}", args.proof_path, err)))?;
+    Ok(format!("Proof written to {}", &args.proof_path))
+}
+
+fn main() {
+    let args: commands::ProverArgs = commands::ProverArgs::parse();
+
+    match args.entity {
+        commands::ProverEntity::Verify(args) => {
+            let recorder = Recorder::new("cairo", None, Path::new(&args.proof_path));
+            recorder.run(args.output_args.bytes_format, args.output_args.envelope, |recorder| verify(&args, recorder))
+        },
+        commands::ProverEntity::Prove(args) => exit_with(|| {
+            println!("{}", prove(args)?);
+            Ok(())
+        }),
+    }
 }
@@ -1,14 +1,22 @@
 use cairo_platinum_prover::{air::{generate_cairo_proof, verify_cairo_proof, PublicInputs}, cairo_mem::CairoMemory, execution_trace::build_main_trace, register_states::RegisterStates};
-use hyle_contract::HyleOutput;
+use hyle_contract::{
+    batch::HyleBatchOutput,
+    encoding,
+    fields::{self, FieldReader, Stark252},
+    versions::{self, HyleOutputV1, OutputVersion},
+    HyleOutput,
+};
 use stark_platinum_prover::proof::options::{ProofOptions, SecurityLevel};
 use serde::{Deserialize, Serialize};
 use lambdaworks_math::field::fields::fft_friendly::stark_252_prime_field::Stark252PrimeField;
 use stark_platinum_prover::proof::stark::StarkProof;
 use error::VerifierError;
-use num::BigInt;
+use num::bigint::{BigInt, Sign};
 
 pub mod error;
 
+/// Program outputs of the erc20 and ML contracts.
+/// Contracts should rather emit `hyle_contract::events`, which need no dedicated parser.
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Event {
     from: Option<String>,
@@ -17,58 +25,145 @@ pub struct Event {
     score: Option<u64>
 }
 
-pub fn verify_proof(proof_path: &String) -> Result<String, VerifierError>{
-    let proof_options = ProofOptions::new_secure(SecurityLevel::Conjecturable100Bits, 3);
-    let Ok(program_content) = std::fs::read(proof_path) else {
-        return Err(VerifierError(format!("Error opening {} file", proof_path)));
-    };
-    let mut bytes = program_content.as_slice();
+/// Reads a proof written by `write_proof`: the STARK proof and its public inputs, followed by
+/// the program output, which is returned as raw bytes.
+///
+/// The output is appended to the proof, it is not part of the public memory of the program: the
+/// STARK proof does not bind it, whoever writes the proof file can change it. This is not sound
+/// until the output is read from the output segment instead. For the same reason, there is no
+/// mode where only a commitment of the output would be embedded: it would not be bound either.
+pub fn read_proof(
+    mut bytes: &[u8],
+) -> Result<(StarkProof<Stark252PrimeField, Stark252PrimeField>, PublicInputs, &[u8]), VerifierError> {
+    let read_error = || VerifierError("Error reading proof".to_string());
     if bytes.len() < 8 {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
+        return Err(read_error());
     }
 
     // Proof len was stored as an u32, 4u8 needs to be read
     let proof_len = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
 
     bytes = &bytes[4..];
-    if bytes.len() < proof_len {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
+    if bytes.len() < proof_len + 4 {
+        return Err(read_error());
     }
-    
+
     let Ok((proof, _)) = bincode::serde::decode_from_slice(
         &bytes[0..proof_len],
         bincode::config::standard(),
     ) else {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
+        return Err(read_error());
     };
-    
+
     // PublicInputs len was stored as an u32, 4u8 needs to be read
     let pub_inputs_len = u32::from_le_bytes(bytes[proof_len..proof_len+4].try_into().unwrap()) as usize;
-    let pub_inputs_bytes = &bytes[proof_len+4..proof_len+4+pub_inputs_len];
-    
+    let pub_inputs_bytes = bytes.get(proof_len+4..proof_len+4+pub_inputs_len).ok_or_else(read_error)?;
+
     let Ok((pub_inputs, _)) =
     bincode::serde::decode_from_slice(pub_inputs_bytes, bincode::config::standard())
     else {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
+        return Err(read_error());
     };
-    let program_output_bytes = &bytes[proof_len+4+pub_inputs_len..];
 
-    let Ok((program_output, _)) =
-        bincode::serde::decode_from_slice::<HyleOutput<Event>, _>(program_output_bytes, bincode::config::standard())
-    else {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
-    };
+    Ok((proof, pub_inputs, &bytes[proof_len+4+pub_inputs_len..]))
+}
+
+pub fn verify_stark_proof(
+    proof: &StarkProof<Stark252PrimeField, Stark252PrimeField>,
+    pub_inputs: &PublicInputs,
+) -> bool {
+    let proof_options = ProofOptions::new_secure(SecurityLevel::Conjecturable100Bits, 3);
+    verify_cairo_proof(proof, pub_inputs, &proof_options)
+}
 
-    if verify_cairo_proof(&proof, &pub_inputs, &proof_options){
-        return Ok(serde_json::to_string(&program_output)?);
+/// Decodes the bincode output embedded in the proof, see `encode_program_output`.
+/// The version is the first field of every layout, it selects the one to decode.
+pub fn decode_program_output(bytes: &[u8]) -> Result<HyleOutput<Event>, VerifierError> {
+    let config = bincode::config::standard();
+    let read_error = || VerifierError("Error reading program output from proof".to_string());
+    let (version, _) = bincode::serde::decode_from_slice::<u32, _>(bytes, config).map_err(|_| read_error())?;
+    match OutputVersion::try_from(version).map_err(|err| VerifierError(err.to_string()))? {
+        OutputVersion::V0 | OutputVersion::V1 => bincode::serde::decode_from_slice::<HyleOutputV1<Event>, _>(bytes, config)
+            .map(|(output, _)| output.into())
+            .map_err(|_| read_error()),
+        OutputVersion::V2 => bincode::serde::decode_from_slice::<HyleOutput<Event>, _>(bytes, config)
+            .map(|(output, _)| output)
+            .map_err(|_| read_error()),
     }
-    else {
-        return Err(VerifierError(format!("Error reading proof from file: {}", proof_path)));
+}
+
+/// Bincode encoding of the output embedded in the proof. Outputs older than version 2 keep the
+/// layout these proofs always had, block fields included, so that existing proofs still verify.
+fn encode_program_output(output: HyleOutput<Event>) -> Result<Vec<u8>, VerifierError> {
+    let config = bincode::config::standard();
+    let version = versions::layout_version(&output).map_err(|err| VerifierError(format!("Error encoding output: {}", err)))?;
+    if version >= OutputVersion::V2 {
+        return Ok(bincode::serde::encode_to_vec(&output, config).unwrap());
     }
+    let output = HyleOutputV1 {
+        version: output.version,
+        initial_state: output.initial_state,
+        next_state: output.next_state,
+        origin: output.origin,
+        caller: output.caller,
+        block_number: output.block_number,
+        block_time: output.block_time,
+        tx_hash: output.tx_hash,
+        program_outputs: output.program_outputs,
+    };
+    Ok(bincode::serde::encode_to_vec(&output, config).unwrap())
 }
 
+/// Decodes a batch embedded in the proof, see `encode_batch_output`. `bytes` start after `BATCH_MAGIC`.
+pub fn decode_batch_output(mut bytes: &[u8]) -> Result<HyleBatchOutput<Event>, VerifierError> {
+    let read_error = || VerifierError("Error reading program output from proof".to_string());
+    let read_u32 = |bytes: &mut &[u8]| -> Result<usize, VerifierError> {
+        let value = bytes.get(0..4).ok_or_else(read_error)?;
+        let value = u32::from_le_bytes(value.try_into().unwrap()) as usize;
+        *bytes = &bytes[4..];
+        Ok(value)
+    };
+    let count = read_u32(&mut bytes)?;
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(&mut bytes)?;
+        let output_bytes = bytes.get(0..len).ok_or_else(read_error)?;
+        outputs.push(decode_program_output(output_bytes)?);
+        bytes = &bytes[len..];
+    }
+    Ok(HyleBatchOutput { outputs })
+}
+
+/// Bincode encoding of a batch embedded in the proof: `BATCH_MAGIC`, the number of outputs as
+/// an u32, then each output as encoded by `encode_program_output`, prefixed by its length as an u32.
+fn encode_batch_output(batch: HyleBatchOutput<Event>) -> Result<Vec<u8>, VerifierError> {
+    let mut bytes = encoding::BATCH_MAGIC.to_vec();
+    bytes.extend((batch.outputs.len() as u32).to_le_bytes());
+    for output in batch.outputs {
+        let output_bytes = encode_program_output(output)?;
+        bytes.extend((output_bytes.len() as u32).to_le_bytes());
+        bytes.extend(output_bytes);
+    }
+    Ok(bytes)
+}
 
 pub fn prove(trace_data: Vec<u8>, memory_data: Vec<u8>, output: &str) -> Result<Vec<u8>, VerifierError> {
+    let program_output = <HyleOutput<Event> as DeserializableHyleOutput>::deserialize(output)?;
+    let program_output_bytes = encode_program_output(program_output)?;
+    prove_with_output(trace_data, memory_data, program_output_bytes)
+}
+
+/// Same as `prove`, for the outputs of several transactions, see `fields::encode_batch`.
+pub fn prove_batch(trace_data: Vec<u8>, memory_data: Vec<u8>, output: &str) -> Result<Vec<u8>, VerifierError> {
+    let batch = fields::decode_batch::<Stark252>(&parse_felts(output)?)
+        .map_err(|err| VerifierError(format!("Error decoding output: {}", err)))?;
+    let outputs = batch.outputs.into_iter().map(with_contract_outputs).collect::<Result<_, _>>()?;
+    let batch = HyleBatchOutput { outputs };
+    batch.check_chain().map_err(|err| VerifierError(format!("Invalid batch: {}", err)))?;
+    prove_with_output(trace_data, memory_data, encode_batch_output(batch)?)
+}
+
+fn prove_with_output(trace_data: Vec<u8>, memory_data: Vec<u8>, program_output_bytes: Vec<u8>) -> Result<Vec<u8>, VerifierError> {
     let proof_options = ProofOptions::new_secure(SecurityLevel::Conjecturable100Bits, 3);
     let Some((proof, pub_inputs)) = generate_proof_from_trace(
         &trace_data,
@@ -77,7 +172,7 @@ pub fn prove(trace_data: Vec<u8>, memory_data: Vec<u8>, output: &str) -> Result<
     ) else {
         return Err(VerifierError("Error generation prover args".to_string()));
     };
-    let proof = write_proof(proof, pub_inputs, output);
+    let proof = write_proof(proof, pub_inputs, program_output_bytes);
     Ok(proof)
 }
 
@@ -90,8 +185,8 @@ pub fn generate_proof_from_trace(
     PublicInputs,
 )> {
     // ## Generating the prover args
-    let register_states = RegisterStates::from_bytes_le(trace_data).expect("Cairo trace data incorrect");
-    let memory = CairoMemory::from_bytes_le(memory_data).expect("Cairo memory data incorrect");
+    let register_states = RegisterStates::from_bytes_le(trace_data).ok()?;
+    let memory = CairoMemory::from_bytes_le(memory_data).ok()?;
 
     // data length
     let data_len = 0_usize;
@@ -116,7 +211,7 @@ pub fn generate_proof_from_trace(
 fn write_proof(
     proof: StarkProof<Stark252PrimeField, Stark252PrimeField>,
     pub_inputs: PublicInputs,
-    output: &str,
+    program_output_bytes: Vec<u8>,
 ) -> Vec<u8> {
     let mut bytes = vec![];
     let proof_bytes: Vec<u8> =
@@ -139,9 +234,6 @@ fn write_proof(
 
     ///// HYLE CUSTOM /////
     // Basically adding the program output to the proof
-    let program_output = <HyleOutput<Event> as DeserializableHyleOutput>::deserialize(&output);
-    let program_output_bytes: Vec<u8> =
-        bincode::serde::encode_to_vec(&program_output, bincode::config::standard()).unwrap();
     bytes.extend(program_output_bytes);
     ///////////////////////
     bytes
@@ -150,107 +242,149 @@ fn write_proof(
 
 
 pub trait DeserializableHyleOutput {
-    fn i_to_w(s: String) -> String;
-    fn deserialize_cairo_bytesarray(data: &mut Vec<&str>) -> String;
-    fn deserialize(input: &str) -> Self;
+    fn deserialize(input: &str) -> Result<Self, VerifierError>
+    where
+        Self: Sized;
 }
 
+/// Prime of the Stark252 field, `2^251 + 17 * 2^192 + 1`.
+fn stark252_prime() -> BigInt {
+    (BigInt::from(1) << 251) + (BigInt::from(17) << 192) + 1
+}
+
+/// Parses the felts printed by the cairo runner, e.g. `[1 0 5]`, as 32 bytes big-endian values.
+/// The runner prints felts above half the prime as negative numbers, they are reduced modulo
+/// the prime. Values out of `[-p, p)` are rejected.
+fn parse_felts(input: &str) -> Result<Vec<[u8; 32]>, VerifierError> {
+    let prime = stark252_prime();
+    let trimmed = input.trim().trim_matches(|c| c == '[' || c == ']');
+    trimmed
+        .split_whitespace()
+        .map(|felt| {
+            let felt_error = || VerifierError(format!("Invalid felt in output: {}", felt));
+            let mut value = felt.parse::<BigInt>().map_err(|_| felt_error())?;
+            if value.sign() == Sign::Minus {
+                value += &prime;
+            }
+            if value.sign() == Sign::Minus || value >= prime {
+                return Err(felt_error());
+            }
+            let (_, bytes) = value.to_bytes_be();
+            let mut value = [0u8; 32];
+            value[32 - bytes.len()..].copy_from_slice(&bytes);
+            Ok(value)
+        })
+        .collect()
+}
 
 impl DeserializableHyleOutput for HyleOutput<Event> {
-    /// Receives an int, change base to hex, decode it to ascii
-    fn i_to_w(s: String) -> String {
-        let int = s.parse::<BigInt>().expect("failed to parse the address");
-        let hex = hex::decode(format!("{:x}", int)).expect("failed to parse the address");
-        String::from_utf8(hex).expect("failed to parse the address")
+    /// Deserialize the output of the cairo erc20 or ML contracts.
+    /// From version 2, the HyleOutput fields follow the felt252 layout of `hyle_contract::fields`,
+    /// older versions have the layout of `deserialize_legacy`. Then:
+    /// - erc20: ByteArray for the "from" address, ByteArray for the "to" address, amount transfered
+    /// - ML: a single element for the score
+    ///
+    /// Contracts emitting their results as events have no program outputs.
+    fn deserialize(input: &str) -> Result<Self, VerifierError> {
+        let felts = parse_felts(input)?;
+        let version = FieldReader::<Stark252>::new(&felts).u32()
+            .map_err(|err| VerifierError(format!("Error decoding output: {}", err)))?;
+        let output = match OutputVersion::try_from(version) {
+            Ok(OutputVersion::V0 | OutputVersion::V1) => deserialize_legacy(version, input, &felts)?,
+            _ => fields::decode::<Stark252>(&felts)
+                .map_err(|err| VerifierError(format!("Error decoding output: {}", err)))?,
+        };
+        with_contract_outputs(output)
     }
+}
 
-    /// BytesArray serialisation is composed of 3 values (if the data is less than 31bytes)
-    /// https://github.com/starkware-libs/cairo/blob/main/corelib/src/byte_array.cairo#L24-L34
-    /// WARNING: Deserialization is not yet robust.
-    /// TODO: pending_word_len not used.
-    /// TODO: add checking on inputs.
-    fn deserialize_cairo_bytesarray(data: &mut Vec<&str>) -> String {
-        let pending_word = data.remove(0).parse::<usize>().unwrap();
-        let _pending_word_len = data.remove(pending_word + 1).parse::<usize>().unwrap();
-        let mut word: String = "".into();
-        for _ in 0..pending_word+1 {
-            let d: String = data.remove(0).into();
-            if d != "0"{
-                word.push_str(&Self::i_to_w(d));
-            }
-        }
-        word
-    }
+/// Deserialize the output of version 0 and 1 contracts, which predate `hyle_contract::fields`:
+/// - the version
+/// - initial_state and next_state as a single felt each, whose decimal text is the state
+/// - origin and caller as ByteArrays
+/// - tx_hash as a single felt, whose decimal text is the hash
+/// - the program outputs
+///
+/// These contracts had no block fields, they are 0.
+fn deserialize_legacy(version: u32, input: &str, felts: &[[u8; 32]]) -> Result<HyleOutput<Vec<[u8; 32]>>, VerifierError> {
+    let tokens: Vec<&str> = input.trim().trim_matches(|c| c == '[' || c == ']').split_whitespace().collect();
+    let parse_error = |field: &str| VerifierError(format!("Failed to parse the {} in output", field));
+    let mut reader = FieldReader::<Stark252>::new(felts.get(3..).ok_or_else(|| parse_error("states"))?);
+    let origin = reader.string().map_err(|_| parse_error("origin"))?;
+    let caller = reader.string().map_err(|_| parse_error("caller"))?;
+    let tx_hash_position = felts.len() - reader.remaining().len();
+    let tx_hash = tokens.get(tx_hash_position).ok_or_else(|| parse_error("tx_hash"))?;
+    let output = HyleOutputV1 {
+        version,
+        initial_state: tokens[1].as_bytes().to_vec(),
+        next_state: tokens[2].as_bytes().to_vec(),
+        origin,
+        caller,
+        block_number: 0,
+        block_time: 0,
+        tx_hash: tx_hash.as_bytes().to_vec(),
+        program_outputs: felts[tx_hash_position + 1..].to_vec(),
+    };
+    Ok(output.into())
+}
 
-    /// Deserialize the output of the cairo erc20 contract.
-    /// elements for the "from" address
-    /// elements for the "to" address
-    /// [-2] element for the amount transfered
-    /// [-1] element for the next state
-    fn deserialize(input: &str) -> Self {
-        let trimmed = input.trim_matches(|c| c == '[' || c == ']');
-        let mut parts: Vec<&str> = trimmed.split_whitespace().collect();
-        // extract version
-        let version = parts.remove(0).parse::<u32>().unwrap();
-        // extract initial_state
-        let initial_state: String = parts.remove(0).parse::<String>().unwrap();
-        // extract next_state
-        let next_state: String = parts.remove(0).parse::<String>().unwrap();
-        // extract origin
-        let origin: String = Self::deserialize_cairo_bytesarray(&mut parts);
-        // extract caller
-        let caller: String = Self::deserialize_cairo_bytesarray(&mut parts);
-        // extract tx_hash
-        let tx_hash: String = parts.remove(0).parse::<String>().unwrap();
-
-        let output = match parts.len() {
-            1 => {
-                let score = parts.remove(0).parse::<u64>().unwrap();
-                let program_outputs = Event {
-                    score: Some(score),
-                    ..Default::default()
-                };
-                HyleOutput {
-                    version,
-                    initial_state: initial_state.as_bytes().to_vec(),
-                    next_state: next_state.as_bytes().to_vec(),
-                    origin,
-                    caller,
-                    block_number: 0,
-                    block_time: 0,
-                    tx_hash: tx_hash.as_bytes().to_vec(),
-                    program_outputs
-                }
-            },
-            7 => {
-                // extract from
-                let from = Self::deserialize_cairo_bytesarray(&mut parts);
-                // extract to
-                let to = Self::deserialize_cairo_bytesarray(&mut parts);
-                // extract amount
-                let amount = parts.remove(0).parse::<u64>().unwrap();
-
-                let program_outputs = Event {
-                    from: Some(from),
-                    to: Some(to),
-                    amount: Some(amount),
-                     ..Default::default()
-                };
-                HyleOutput {
-                    version,
-                    initial_state: initial_state.as_bytes().to_vec(),
-                    next_state: next_state.as_bytes().to_vec(),
-                    origin,
-                    caller,
-                    block_number: 0,
-                    block_time: 0,
-                    tx_hash: tx_hash.as_bytes().to_vec(),
-                    program_outputs
-                }
-            }
-            _ => panic!("You're not parsing ERC20 or ML. Sorry bro not possible atm. Or your name is too long :eyes:"),
-        };
+/// Parses the program outputs of the erc20 or ML contracts, see `DeserializableHyleOutput`.
+fn with_contract_outputs(output: HyleOutput<Vec<[u8; 32]>>) -> Result<HyleOutput<Event>, VerifierError> {
+    let mut reader = FieldReader::<Stark252>::new(&output.program_outputs);
+    let parse_error = |field: &str| VerifierError(format!("Failed to parse the {} in program outputs", field));
+    let program_outputs = match output.program_outputs.len() {
+        0 => Event::default(),
+        1 => Event {
+            score: Some(reader.u64().map_err(|_| parse_error("score"))?),
+            ..Default::default()
+        },
+        _ => Event {
+            from: Some(reader.string().map_err(|_| parse_error("from address"))?),
+            to: Some(reader.string().map_err(|_| parse_error("to address"))?),
+            amount: Some(reader.u64().map_err(|_| parse_error("amount"))?),
+            ..Default::default()
+        },
+    };
 
-        output
+    Ok(HyleOutput {
+        version: output.version,
+        initial_state: output.initial_state,
+        next_state: output.next_state,
+        origin: output.origin,
+        caller: output.caller,
+        block_number: output.block_number,
+        block_time: output.block_time,
+        tx_hash: output.tx_hash,
+        events: output.events,
+        program_outputs,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_legacy() {
+        // Output of the erc20 contract before `hyle_contract::fields`: version 1, states "100" and
+        // "90", origin and caller "alice", tx_hash "12345", then a transfer of 10 from alice to bob.
+        let input = "[1 100 90 0 418430673765 5 0 418430673765 5 12345 0 418430673765 5 0 6451042 3 10]";
+        let output = <HyleOutput<Event> as DeserializableHyleOutput>::deserialize(input).unwrap();
+        assert_eq!(output.version, 1);
+        assert_eq!(output.initial_state, b"100");
+        assert_eq!(output.next_state, b"90");
+        assert_eq!(output.origin, "alice");
+        assert_eq!(output.caller, "alice");
+        assert_eq!((output.block_number, output.block_time), (0, 0));
+        assert_eq!(output.tx_hash, b"12345");
+        assert!(output.events.is_empty());
+        assert_eq!(output.program_outputs.from.as_deref(), Some("alice"));
+        assert_eq!(output.program_outputs.to.as_deref(), Some("bob"));
+        assert_eq!(output.program_outputs.amount, Some(10));
+
+        // ML contract, a single score.
+        let output = <HyleOutput<Event> as DeserializableHyleOutput>::deserialize("[0 1 2 0 6451042 3 0 6451042 3 7 42]").unwrap();
+        assert_eq!(output.tx_hash, b"7");
+        assert_eq!(output.program_outputs.score, Some(42));
     }
-}
\ No newline at end of file
+}
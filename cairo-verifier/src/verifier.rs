@@ -0,0 +1,49 @@
+// Verification of Cairo proofs, see `verifier_core::Verifier`.
+
+use std::path::Path;
+
+use hyle_contract::encoding;
+use verifier_core::{batch_to_value, to_value, ProofOutputs, Verifier, VerifyError};
+
+use crate::utils::{decode_batch_output, decode_program_output, read_proof, verify_stark_proof};
+
+pub struct CairoVerifier;
+
+pub struct CairoProof {
+    /// Proof as written by `utils::write_proof`.
+    pub proof: Vec<u8>,
+}
+
+impl CairoProof {
+    pub fn read(proof_path: &Path) -> Result<Self, VerifyError> {
+        let proof = std::fs::read(proof_path)
+            .map_err(|_| VerifyError::Decode(format!("Error opening {} file", proof_path.display())))?;
+        Ok(CairoProof { proof })
+    }
+}
+
+impl Verifier for CairoVerifier {
+    /// Cairo proofs embed the program they were generated for in their public inputs.
+    type ProgramId = ();
+    type Proof = CairoProof;
+
+    fn verify_outputs(&self, _program_id: &(), proof: &CairoProof) -> Result<ProofOutputs, VerifyError> {
+        let (stark_proof, pub_inputs, program_output_bytes) =
+            read_proof(&proof.proof).map_err(|err| VerifyError::Decode(err.0))?;
+        if !verify_stark_proof(&stark_proof, &pub_inputs) {
+            return Err(VerifyError::InvalidProof("cairo proof does not verify".to_string()));
+        }
+
+        let output_error = |err: crate::utils::error::VerifierError| VerifyError::OutputDecode(err.0);
+        match program_output_bytes.strip_prefix(&encoding::BATCH_MAGIC) {
+            Some(batch_bytes) => {
+                let batch = decode_batch_output(batch_bytes).map_err(output_error)?;
+                Ok(ProofOutputs::Batch(batch_to_value(batch)?))
+            }
+            None => {
+                let output = decode_program_output(program_output_bytes).map_err(output_error)?;
+                Ok(ProofOutputs::Single(to_value(output)?))
+            }
+        }
+    }
+}
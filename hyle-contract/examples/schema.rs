@@ -0,0 +1,21 @@
+// Prints the JSON Schema of the verifiers input and output, as committed in `schema/`:
+//   cargo run --example schema --features schema -- input > schema/hyle_input.schema.json
+//   cargo run --example schema --features schema -- output > schema/hyle_output.schema.json
+
+use hyle_contract::readable::ReadableOutput;
+use hyle_contract::HyleInput;
+use schemars::schema_for;
+use serde_json::Value;
+
+fn main() {
+    // Program inputs and outputs are contract-specific, any JSON value is accepted.
+    let schema = match std::env::args().nth(1).as_deref() {
+        Some("input") => schema_for!(HyleInput<Value>),
+        Some("output") => schema_for!(ReadableOutput<Value>),
+        _ => {
+            eprintln!("usage: schema <input|output>");
+            std::process::exit(1);
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+}
@@ -0,0 +1,146 @@
+// Outputs of several transactions proven at once, to amortize proving costs.
+//
+// Transactions are applied in order: the `next_state` of each output must be the
+// `initial_state` of the following one. Verifiers check this chain, then report one result
+// per transaction. See `encoding::encode_batch` and `fields::encode_batch` for the layouts.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::state::{KvState, StateDiff, StateError};
+use crate::validation::{ExpectedContext, Mismatch};
+use crate::versions::EncodeError;
+use crate::{encoding, HyleOutput};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HyleBatchOutput<T> {
+    pub outputs: Vec<HyleOutput<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    Empty,
+    /// The `next_state` of this output is not the `initial_state` of the following one.
+    BrokenChain(usize),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Empty => write!(f, "batch holds no output"),
+            BatchError::BrokenChain(i) => {
+                write!(f, "next_state of output {} is not the initial_state of output {}", i, i + 1)
+            }
+        }
+    }
+}
+
+impl<T> HyleBatchOutput<T> {
+    /// Checks that the batch is not empty and that its states are chained.
+    pub fn check_chain(&self) -> Result<(), BatchError> {
+        if self.outputs.is_empty() {
+            return Err(BatchError::Empty);
+        }
+        match self.outputs.windows(2).position(|pair| pair[0].next_state != pair[1].initial_state) {
+            Some(i) => Err(BatchError::BrokenChain(i)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks every output against `expected`, returning the index of each mismatching output.
+    /// `initial_state` is only expected from the first output, the others follow the chain.
+    /// `tx_hash` identifies a single transaction, it is not checked: callers expecting it from a
+    /// batch should reject the expectation.
+    pub fn validate(&self, expected: &ExpectedContext) -> Result<(), Vec<(usize, Mismatch)>> {
+        let expected = &ExpectedContext { tx_hash: None, ..expected.clone() };
+        let following = ExpectedContext { initial_state: None, ..expected.clone() };
+        let mismatches: Vec<(usize, Mismatch)> = self
+            .outputs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, output)| {
+                let expected = if i == 0 { expected } else { &following };
+                output.validate(expected).err().unwrap_or_default().into_iter().map(move |m| (i, m))
+            })
+            .collect();
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// Checks the transition of the whole batch, `diff` being the changes made by all of its
+    /// transactions. Returns the updated state.
+    pub fn check_state_diff(&self, prior: &KvState, diff: &StateDiff) -> Result<KvState, StateError> {
+        let (Some(first), Some(last)) = (self.outputs.first(), self.outputs.last()) else {
+            return Err(StateError::InitialRootMismatch);
+        };
+        diff.check_transition(prior, &first.initial_state, &last.next_state)
+    }
+}
+
+impl<T: AsRef<[u8]>> HyleBatchOutput<T> {
+    /// SHA-256 digest of the canonical encoding of this batch, see `HyleOutput::commitment`.
+    pub fn commitment(&self) -> Result<[u8; 32], EncodeError> {
+        Ok(Sha256::digest(encoding::encode_batch(self)?).into())
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn output(initial_state: u8, next_state: u8) -> HyleOutput<()> {
+        HyleOutput {
+            version: 2,
+            initial_state: vec![initial_state],
+            next_state: vec![next_state],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![initial_state],
+            events: vec![],
+            program_outputs: (),
+        }
+    }
+
+    #[test]
+    fn test_check_chain() {
+        assert_eq!(HyleBatchOutput::<()> { outputs: vec![] }.check_chain(), Err(BatchError::Empty));
+        assert_eq!(HyleBatchOutput { outputs: vec![output(1, 2)] }.check_chain(), Ok(()));
+        let batch = HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3), output(3, 4)] };
+        assert_eq!(batch.check_chain(), Ok(()));
+        let batch = HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3), output(4, 5)] };
+        assert_eq!(batch.check_chain(), Err(BatchError::BrokenChain(1)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut batch = HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3)] };
+        let expected = ExpectedContext {
+            initial_state: Some(vec![1]),
+            origin: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(batch.validate(&expected), Ok(()));
+
+        batch.outputs[1].origin = "carol".to_string();
+        assert_eq!(
+            batch.validate(&expected),
+            Err(vec![(1, Mismatch::Origin { expected: "alice".to_string(), actual: "carol".to_string() })])
+        );
+
+        // The tx_hash of the first output is not expected from the second one.
+        let batch = HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3)] };
+        let expected = ExpectedContext { tx_hash: Some(vec![1]), ..Default::default() };
+        assert_eq!(batch.validate(&expected), Ok(()));
+    }
+}
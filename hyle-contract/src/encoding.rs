@@ -0,0 +1,456 @@
+// Canonical, zkVM-agnostic binary encoding of `HyleOutput`.
+//
+// Every backend should commit exactly these bytes as its public output, so that all
+// verifiers can decode them with the same code.
+//
+// Layout (encoding version 1). Integers are little-endian, `bytes` and `string` fields are
+// prefixed by their length as a u32, strings are UTF-8. Fields and lists longer than
+// `u32::MAX` cannot be encoded:
+//
+// | field           | type   |
+// |-----------------|--------|
+// | magic           | b"HYLE"|
+// | encoding        | u8     |
+// | version         | u32    |
+// | initial_state   | bytes  |
+// | next_state      | bytes  |
+// | origin          | string |
+// | caller          | string |
+// | block_number    | u64    |
+// | block_time      | u64    |
+// | tx_hash         | bytes  |
+// | events          | events |
+// | program_outputs | bytes  |
+//
+// `events` is a u32 count followed by each event: its topic as a string, a u32 count of
+// attributes, then for each attribute its key as a string, a u8 tag (see `EventValue`) and the
+// value as a u64, bytes or string.
+//
+// Version 0 and 1 outputs (see `versions`) have no `events`, encoding them with events fails.
+// Trailing bytes after `program_outputs` are rejected.
+//
+// A `HyleBatchOutput` starts with `BATCH_MAGIC` and the encoding version, followed by a u32
+// count of outputs, each one encoded as above and prefixed by its length as a u32.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::batch::HyleBatchOutput;
+use crate::events::{Event, EventAttribute, EventValue};
+use crate::versions::{self, EncodeError, HyleOutputV1, OutputVersion};
+use crate::HyleOutput;
+
+/// Prefix of every canonically encoded output.
+pub const MAGIC: [u8; 4] = *b"HYLE";
+
+/// Prefix of every canonically encoded batch of outputs.
+pub const BATCH_MAGIC: [u8; 4] = *b"HYLB";
+
+/// Version of the byte layout itself, distinct from `HyleOutput::version`.
+pub const ENCODING_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedEncoding(u8),
+    UnsupportedVersion(u32),
+    UnexpectedEof,
+    InvalidUtf8,
+    InvalidEventTag(u8),
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a canonical HyleOutput (bad magic)"),
+            DecodeError::UnsupportedEncoding(v) => write!(f, "unsupported encoding version {}", v),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported HyleOutput version {}", v),
+            DecodeError::UnexpectedEof => write!(f, "unexpected end of input"),
+            DecodeError::InvalidUtf8 => write!(f, "string field is not valid UTF-8"),
+            DecodeError::InvalidEventTag(tag) => write!(f, "invalid event value tag {}", tag),
+            DecodeError::TrailingBytes(n) => write!(f, "{} trailing bytes after output", n),
+        }
+    }
+}
+
+/// Returns true if `bytes` look like a canonically encoded output.
+pub fn is_canonical(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Returns true if `bytes` look like a canonically encoded batch of outputs.
+pub fn is_canonical_batch(bytes: &[u8]) -> bool {
+    bytes.starts_with(&BATCH_MAGIC)
+}
+
+/// Encodes `output` using the canonical layout of its version.
+/// `program_outputs` are contract-specific and carried as opaque bytes.
+pub fn encode<T: AsRef<[u8]>>(output: &HyleOutput<T>) -> Result<Vec<u8>, EncodeError> {
+    let version = versions::layout_version(output)?;
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&MAGIC);
+    buf.push(ENCODING_VERSION);
+    buf.extend_from_slice(&output.version.to_le_bytes());
+    put_bytes(&mut buf, &output.initial_state)?;
+    put_bytes(&mut buf, &output.next_state)?;
+    put_bytes(&mut buf, output.origin.as_bytes())?;
+    put_bytes(&mut buf, output.caller.as_bytes())?;
+    buf.extend_from_slice(&output.block_number.to_le_bytes());
+    buf.extend_from_slice(&output.block_time.to_le_bytes());
+    put_bytes(&mut buf, &output.tx_hash)?;
+    if version >= OutputVersion::V2 {
+        put_events(&mut buf, &output.events)?;
+    }
+    put_bytes(&mut buf, output.program_outputs.as_ref())?;
+    Ok(buf)
+}
+
+/// Decodes a canonically encoded output, upgraded to the current struct.
+/// `program_outputs` are returned as raw bytes.
+pub fn decode(bytes: &[u8]) -> Result<HyleOutput<Vec<u8>>, DecodeError> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let encoding = reader.take(1)?[0];
+    if encoding != ENCODING_VERSION {
+        return Err(DecodeError::UnsupportedEncoding(encoding));
+    }
+    let version = reader.u32()?;
+    let output = match OutputVersion::try_from(version) {
+        Ok(OutputVersion::V0 | OutputVersion::V1) => decode_v1(version, &mut reader)?.into(),
+        Ok(OutputVersion::V2) => decode_v2(version, &mut reader)?,
+        Err(_) => return Err(DecodeError::UnsupportedVersion(version)),
+    };
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError::TrailingBytes(reader.bytes.len()));
+    }
+    Ok(output)
+}
+
+/// Encodes every output of `batch` with `encode`.
+pub fn encode_batch<T: AsRef<[u8]>>(batch: &HyleBatchOutput<T>) -> Result<Vec<u8>, EncodeError> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&BATCH_MAGIC);
+    buf.push(ENCODING_VERSION);
+    put_len(&mut buf, batch.outputs.len())?;
+    for output in &batch.outputs {
+        put_bytes(&mut buf, &encode(output)?)?;
+    }
+    Ok(buf)
+}
+
+/// Decodes a canonically encoded batch of outputs. The chain of states is not checked here,
+/// see `HyleBatchOutput::check_chain`.
+pub fn decode_batch(bytes: &[u8]) -> Result<HyleBatchOutput<Vec<u8>>, DecodeError> {
+    let mut reader = Reader { bytes };
+    if reader.take(BATCH_MAGIC.len())? != BATCH_MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let encoding = reader.take(1)?[0];
+    if encoding != ENCODING_VERSION {
+        return Err(DecodeError::UnsupportedEncoding(encoding));
+    }
+    let count = reader.u32()?;
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        outputs.push(decode(&reader.bytes()?)?);
+    }
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError::TrailingBytes(reader.bytes.len()));
+    }
+    Ok(HyleBatchOutput { outputs })
+}
+
+fn decode_v1(version: u32, reader: &mut Reader) -> Result<HyleOutputV1<Vec<u8>>, DecodeError> {
+    Ok(HyleOutputV1 {
+        version,
+        initial_state: reader.bytes()?,
+        next_state: reader.bytes()?,
+        origin: reader.string()?,
+        caller: reader.string()?,
+        block_number: reader.u64()?,
+        block_time: reader.u64()?,
+        tx_hash: reader.bytes()?,
+        program_outputs: reader.bytes()?,
+    })
+}
+
+fn decode_v2(version: u32, reader: &mut Reader) -> Result<HyleOutput<Vec<u8>>, DecodeError> {
+    Ok(HyleOutput {
+        version,
+        initial_state: reader.bytes()?,
+        next_state: reader.bytes()?,
+        origin: reader.string()?,
+        caller: reader.string()?,
+        block_number: reader.u64()?,
+        block_time: reader.u64()?,
+        tx_hash: reader.bytes()?,
+        events: reader.events()?,
+        program_outputs: reader.bytes()?,
+    })
+}
+
+fn put_len(buf: &mut Vec<u8>, len: usize) -> Result<(), EncodeError> {
+    let len = u32::try_from(len).map_err(|_| EncodeError::TooLong(len))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) -> Result<(), EncodeError> {
+    put_len(buf, data.len())?;
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+fn put_events(buf: &mut Vec<u8>, events: &[Event]) -> Result<(), EncodeError> {
+    put_len(buf, events.len())?;
+    for event in events {
+        put_bytes(buf, event.topic.as_bytes())?;
+        put_len(buf, event.attributes.len())?;
+        for attribute in &event.attributes {
+            put_bytes(buf, attribute.key.as_bytes())?;
+            buf.push(attribute.value.tag() as u8);
+            match &attribute.value {
+                EventValue::U64(value) => buf.extend_from_slice(&value.to_le_bytes()),
+                EventValue::Bytes(value) => put_bytes(buf, value)?,
+                EventValue::String(value) => put_bytes(buf, value.as_bytes())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    fn events(&mut self) -> Result<Vec<Event>, DecodeError> {
+        let count = self.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let topic = self.string()?;
+            let attribute_count = self.u32()?;
+            let mut attributes = Vec::new();
+            for _ in 0..attribute_count {
+                let key = self.string()?;
+                let value = match self.take(1)?[0] {
+                    tag if tag as u32 == EventValue::U64_TAG => EventValue::U64(self.u64()?),
+                    tag if tag as u32 == EventValue::BYTES_TAG => EventValue::Bytes(self.bytes()?),
+                    tag if tag as u32 == EventValue::STRING_TAG => EventValue::String(self.string()?),
+                    tag => return Err(DecodeError::InvalidEventTag(tag)),
+                };
+                attributes.push(EventAttribute { key, value });
+            }
+            events.push(Event { topic, attributes });
+        }
+        Ok(events)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn sample() -> HyleOutput<Vec<u8>> {
+        HyleOutput {
+            version: 1,
+            initial_state: vec![0, 0, 0, 1],
+            next_state: vec![0, 0, 0, 2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 1_700_000_000,
+            tx_hash: vec![0xab, 0xcd],
+            events: vec![],
+            program_outputs: vec![7],
+        }
+    }
+
+    // Golden vector: any change to this is a breaking change of the protocol encoding.
+    const SAMPLE_BYTES: &[u8] = &[
+        b'H', b'Y', b'L', b'E', 1, // magic, encoding
+        1, 0, 0, 0, // version
+        4, 0, 0, 0, 0, 0, 0, 1, // initial_state
+        4, 0, 0, 0, 0, 0, 0, 2, // next_state
+        5, 0, 0, 0, b'a', b'l', b'i', b'c', b'e', // origin
+        3, 0, 0, 0, b'b', b'o', b'b', // caller
+        42, 0, 0, 0, 0, 0, 0, 0, // block_number
+        0x00, 0xf1, 0x53, 0x65, 0, 0, 0, 0, // block_time
+        2, 0, 0, 0, 0xab, 0xcd, // tx_hash
+        1, 0, 0, 0, 7, // program_outputs
+    ];
+
+    #[test]
+    fn test_golden_vector() {
+        assert_eq!(encode(&sample()).unwrap(), SAMPLE_BYTES);
+        let decoded = decode(SAMPLE_BYTES).unwrap();
+        assert_eq!(encode(&decoded).unwrap(), SAMPLE_BYTES);
+        assert_eq!(decoded.origin, "alice");
+        assert_eq!(decoded.program_outputs, vec![7]);
+    }
+
+    fn sample_v2() -> HyleOutput<Vec<u8>> {
+        HyleOutput {
+            version: 2,
+            events: vec![Event::new("transfer").with("amount", 10).with("to", "bob").with("memo", vec![0xff])],
+            ..sample()
+        }
+    }
+
+    // Golden vector of the current layout, with events.
+    const SAMPLE_V2_BYTES: &[u8] = &[
+        b'H', b'Y', b'L', b'E', 1, // magic, encoding
+        2, 0, 0, 0, // version
+        4, 0, 0, 0, 0, 0, 0, 1, // initial_state
+        4, 0, 0, 0, 0, 0, 0, 2, // next_state
+        5, 0, 0, 0, b'a', b'l', b'i', b'c', b'e', // origin
+        3, 0, 0, 0, b'b', b'o', b'b', // caller
+        42, 0, 0, 0, 0, 0, 0, 0, // block_number
+        0x00, 0xf1, 0x53, 0x65, 0, 0, 0, 0, // block_time
+        2, 0, 0, 0, 0xab, 0xcd, // tx_hash
+        1, 0, 0, 0, // events
+        8, 0, 0, 0, b't', b'r', b'a', b'n', b's', b'f', b'e', b'r', // topic
+        3, 0, 0, 0, // attributes
+        6, 0, 0, 0, b'a', b'm', b'o', b'u', b'n', b't', 0, 10, 0, 0, 0, 0, 0, 0, 0, // u64
+        2, 0, 0, 0, b't', b'o', 2, 3, 0, 0, 0, b'b', b'o', b'b', // string
+        4, 0, 0, 0, b'm', b'e', b'm', b'o', 1, 1, 0, 0, 0, 0xff, // bytes
+        1, 0, 0, 0, 7, // program_outputs
+    ];
+
+    #[test]
+    fn test_golden_vector_v2() {
+        assert_eq!(encode(&sample_v2()).unwrap(), SAMPLE_V2_BYTES);
+        assert_eq!(decode(SAMPLE_V2_BYTES), Ok(sample_v2()));
+    }
+
+    #[test]
+    fn test_too_long() {
+        let mut buf = Vec::new();
+        assert_eq!(put_len(&mut buf, u32::MAX as usize), Ok(()));
+        assert_eq!(put_len(&mut buf, u32::MAX as usize + 1), Err(EncodeError::TooLong(u32::MAX as usize + 1)));
+        assert_eq!(buf, u32::MAX.to_le_bytes());
+    }
+
+    #[test]
+    fn test_empty_fields() {
+        let output = HyleOutput {
+            version: 0,
+            initial_state: vec![],
+            next_state: vec![],
+            origin: String::new(),
+            caller: String::new(),
+            block_number: 0,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: vec![],
+        };
+        let bytes = encode(&output).unwrap();
+        // Header, version, two u64s and six length prefixes.
+        assert_eq!(bytes.len(), 5 + 4 + 16 + 6 * 4);
+        assert_eq!(encode(&decode(&bytes).unwrap()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode_v0() {
+        let mut output = sample();
+        output.version = 0;
+        let bytes = encode(&output).unwrap();
+        // Same layout as version 1, only the version differs.
+        assert_eq!(bytes[9..], SAMPLE_BYTES[9..]);
+        assert_eq!(decode(&bytes), Ok(output));
+    }
+
+    #[test]
+    fn test_events_v2() {
+        let mut output = sample();
+        output.version = 2;
+        output.events = vec![
+            Event::new("transfer").with("from", "alice").with("to", "bob").with("amount", 10),
+            Event::new("memo").with("data", vec![0xff]),
+        ];
+        let bytes = encode(&output).unwrap();
+        assert_eq!(decode(&bytes), Ok(output.clone()));
+
+        // Version 1 outputs cannot carry events, they are rejected rather than dropped.
+        output.version = 1;
+        assert_eq!(encode(&output), Err(EncodeError::UnrepresentableField { version: 1, field: "events" }));
+        assert!(decode(SAMPLE_BYTES).unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut second = sample();
+        second.initial_state = second.next_state.clone();
+        second.next_state = vec![0, 0, 0, 3];
+        let batch = HyleBatchOutput { outputs: vec![sample(), second] };
+        let bytes = encode_batch(&batch).unwrap();
+        assert!(is_canonical_batch(&bytes) && !is_canonical(&bytes));
+        // Each output is its own canonical encoding, prefixed by its length.
+        assert_eq!(&bytes[9..13], (SAMPLE_BYTES.len() as u32).to_le_bytes());
+        assert_eq!(&bytes[13..13 + SAMPLE_BYTES.len()], SAMPLE_BYTES);
+        assert_eq!(decode_batch(&bytes), Ok(batch));
+        assert_eq!(decode_batch(SAMPLE_BYTES), Err(DecodeError::BadMagic));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode(b"HYL"), Err(DecodeError::UnexpectedEof));
+        assert_eq!(decode(b"NOPE\x01"), Err(DecodeError::BadMagic));
+        assert_eq!(decode(b"HYLE\x02"), Err(DecodeError::UnsupportedEncoding(2)));
+        assert_eq!(decode(&SAMPLE_BYTES[..SAMPLE_BYTES.len() - 1]), Err(DecodeError::UnexpectedEof));
+
+        let mut trailing = SAMPLE_BYTES.to_vec();
+        trailing.push(0);
+        assert_eq!(decode(&trailing), Err(DecodeError::TrailingBytes(1)));
+
+        let mut unsupported = SAMPLE_BYTES.to_vec();
+        unsupported[5] = 9;
+        assert_eq!(decode(&unsupported), Err(DecodeError::UnsupportedVersion(9)));
+
+        let mut bad_utf8 = SAMPLE_BYTES.to_vec();
+        bad_utf8[30] = 0xff;
+        assert_eq!(decode(&bad_utf8), Err(DecodeError::InvalidUtf8));
+
+        let mut output = sample();
+        output.version = 2;
+        output.events = vec![Event::new("e").with("k", 1)];
+        let mut bad_tag = encode(&output).unwrap();
+        // Tag of the single attribute, after tx_hash, the counts, the topic and the key.
+        let tag_offset = SAMPLE_BYTES.len() - 5 + 4 + 5 + 4 + 5;
+        bad_tag[tag_offset] = 7;
+        assert_eq!(decode(&bad_tag), Err(DecodeError::InvalidEventTag(7)));
+    }
+}
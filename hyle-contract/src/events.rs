@@ -0,0 +1,87 @@
+// Event log of a transaction, carried in `HyleOutput::events` from version 2 onwards.
+//
+// Events are backend-neutral: indexers can consume them from any verifier output without
+// knowing the layout of the contract `program_outputs`.
+
+use alloc::{string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+/// An event emitted by a contract, tagged by its topic, e.g. "transfer".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Event {
+    pub topic: String,
+    pub attributes: Vec<EventAttribute>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EventAttribute {
+    pub key: String,
+    pub value: EventValue,
+}
+
+/// Typed value of an event attribute. The tag is used by the binary and field encodings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EventValue {
+    U64(u64),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+impl EventValue {
+    pub const U64_TAG: u32 = 0;
+    pub const BYTES_TAG: u32 = 1;
+    pub const STRING_TAG: u32 = 2;
+
+    pub fn tag(&self) -> u32 {
+        match self {
+            EventValue::U64(_) => Self::U64_TAG,
+            EventValue::Bytes(_) => Self::BYTES_TAG,
+            EventValue::String(_) => Self::STRING_TAG,
+        }
+    }
+}
+
+impl From<u64> for EventValue {
+    fn from(value: u64) -> Self {
+        EventValue::U64(value)
+    }
+}
+
+impl From<Vec<u8>> for EventValue {
+    fn from(value: Vec<u8>) -> Self {
+        EventValue::Bytes(value)
+    }
+}
+
+impl From<String> for EventValue {
+    fn from(value: String) -> Self {
+        EventValue::String(value)
+    }
+}
+
+impl From<&str> for EventValue {
+    fn from(value: &str) -> Self {
+        EventValue::String(value.into())
+    }
+}
+
+impl Event {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Event { topic: topic.into(), attributes: Vec::new() }
+    }
+
+    /// Adds an attribute to the event.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<EventValue>) -> Self {
+        self.attributes.push(EventAttribute { key: key.into(), value: value.into() });
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&EventValue> {
+        self.attributes.iter().find(|attribute| attribute.key == key).map(|attribute| &attribute.value)
+    }
+}
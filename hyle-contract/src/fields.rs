@@ -0,0 +1,471 @@
+// Encoding of `HyleOutput` as a list of field elements, shared by all field-based backends
+// (Cairo, Miden, Noir).
+//
+// Layout, one entry per field of `HyleOutput`:
+// - integers (`version`, `block_number`, `block_time`) are split in limbs of at most
+//   `WORD_BYTES` bytes, most significant limb first. A u64 is a single felt252 or BN254 element,
+//   and two Goldilocks elements.
+// - byte arrays and strings follow the Cairo `ByteArray` serialization:
+//   `[full_words_len, full_words..., pending_word, pending_word_len]`, where each full word packs
+//   `WORD_BYTES` bytes big-endian and the pending word holds the remaining bytes.
+//   See https://github.com/starkware-libs/cairo/blob/main/corelib/src/byte_array.cairo
+// - `events` are a u32 count followed by each event: its topic, a u32 count of attributes, then
+//   for each attribute its key, a u32 tag (see `EventValue`) and the value as a u64 or byte array.
+// - `program_outputs` are the remaining elements, left to the contract.
+//
+// This is the layout of version 2 outputs onwards. Outputs of versions 0 and 1 (see `versions`)
+// predate it, each backend laid them out its own way: they are rejected here, and decoded by the
+// legacy decoder of their backend.
+//
+// A `HyleBatchOutput` is a u32 count of outputs, each one encoded as above and prefixed by its
+// number of elements as a u32, so that its `program_outputs` are delimited.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::batch::HyleBatchOutput;
+use crate::events::{Event, EventAttribute, EventValue};
+use crate::versions::{self, EncodeError, OutputVersion};
+use crate::HyleOutput;
+
+/// How a prime field packs integers and bytes into its elements.
+pub trait FieldCodec {
+    type Element: Clone;
+
+    /// Number of bytes packed in a single element, chosen so that any such value is below the modulus.
+    const WORD_BYTES: usize;
+
+    /// Builds an element from at most `WORD_BYTES` big-endian bytes.
+    fn from_be_bytes(bytes: &[u8]) -> Self::Element;
+
+    /// Returns the value of `element` as `len` big-endian bytes, or None if it does not fit.
+    fn to_be_bytes(element: &Self::Element, len: usize) -> Option<Vec<u8>>;
+}
+
+/// Cairo field, elements are 32 bytes big-endian. Byte arrays are packed as `bytes31`.
+pub struct Stark252;
+
+/// Miden field, elements are u64. Byte arrays are packed as u32 limbs.
+pub struct Goldilocks;
+
+/// Noir (BN254 scalar) field, elements are 32 bytes big-endian. Byte arrays are packed 31 bytes per element.
+pub struct Bn254;
+
+fn felt_from_be_bytes(bytes: &[u8]) -> [u8; 32] {
+    let mut felt = [0u8; 32];
+    felt[32 - bytes.len()..].copy_from_slice(bytes);
+    felt
+}
+
+fn felt_to_be_bytes(felt: &[u8; 32], len: usize) -> Option<Vec<u8>> {
+    let (high, low) = felt.split_at(32 - len);
+    high.iter().all(|b| *b == 0).then(|| low.to_vec())
+}
+
+impl FieldCodec for Stark252 {
+    type Element = [u8; 32];
+    const WORD_BYTES: usize = 31;
+
+    fn from_be_bytes(bytes: &[u8]) -> Self::Element {
+        felt_from_be_bytes(bytes)
+    }
+
+    fn to_be_bytes(element: &Self::Element, len: usize) -> Option<Vec<u8>> {
+        felt_to_be_bytes(element, len)
+    }
+}
+
+impl FieldCodec for Bn254 {
+    type Element = [u8; 32];
+    const WORD_BYTES: usize = 31;
+
+    fn from_be_bytes(bytes: &[u8]) -> Self::Element {
+        felt_from_be_bytes(bytes)
+    }
+
+    fn to_be_bytes(element: &Self::Element, len: usize) -> Option<Vec<u8>> {
+        felt_to_be_bytes(element, len)
+    }
+}
+
+impl FieldCodec for Goldilocks {
+    type Element = u64;
+    const WORD_BYTES: usize = 4;
+
+    fn from_be_bytes(bytes: &[u8]) -> Self::Element {
+        bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+    }
+
+    fn to_be_bytes(element: &Self::Element, len: usize) -> Option<Vec<u8>> {
+        let bytes = element.to_be_bytes();
+        let (high, low) = bytes.split_at(8 - len);
+        high.iter().all(|b| *b == 0).then(|| low.to_vec())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldDecodeError {
+    UnexpectedEnd,
+    /// The element at this index is out of range for the value it holds.
+    OutOfRange(usize),
+    InvalidUtf8,
+    UnsupportedVersion(u32),
+    /// The output predates this layout, see the legacy decoder of its backend.
+    LegacyVersion(u32),
+}
+
+impl fmt::Display for FieldDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldDecodeError::UnexpectedEnd => write!(f, "unexpected end of field elements"),
+            FieldDecodeError::OutOfRange(i) => write!(f, "field element {} is out of range", i),
+            FieldDecodeError::InvalidUtf8 => write!(f, "string field is not valid UTF-8"),
+            FieldDecodeError::UnsupportedVersion(v) => write!(f, "unsupported HyleOutput version {}", v),
+            FieldDecodeError::LegacyVersion(v) => {
+                write!(f, "version {} HyleOutput predates the field layout, it has a backend-specific layout", v)
+            }
+        }
+    }
+}
+
+/// Appends values to a list of field elements, following the layout above.
+pub struct FieldWriter<F: FieldCodec> {
+    pub elements: Vec<F::Element>,
+}
+
+impl<F: FieldCodec> Default for FieldWriter<F> {
+    fn default() -> Self {
+        Self { elements: Vec::new() }
+    }
+}
+
+impl<F: FieldCodec> FieldWriter<F> {
+    pub fn uint(&mut self, value: u64, size: usize) {
+        let bytes = value.to_be_bytes();
+        let bytes = &bytes[8 - size..];
+        let first = bytes.len() % F::WORD_BYTES;
+        if first > 0 {
+            self.elements.push(F::from_be_bytes(&bytes[..first]));
+        }
+        for limb in bytes[first..].chunks(F::WORD_BYTES) {
+            self.elements.push(F::from_be_bytes(limb));
+        }
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.uint(value as u64, 4);
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.uint(value, 8);
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) {
+        let full_words = data.len() / F::WORD_BYTES;
+        let pending = &data[full_words * F::WORD_BYTES..];
+        self.u32(full_words as u32);
+        for word in data.chunks_exact(F::WORD_BYTES) {
+            self.elements.push(F::from_be_bytes(word));
+        }
+        self.elements.push(F::from_be_bytes(pending));
+        self.u32(pending.len() as u32);
+    }
+
+    pub fn string(&mut self, data: &str) {
+        self.bytes(data.as_bytes());
+    }
+
+    pub fn events(&mut self, events: &[Event]) {
+        self.u32(events.len() as u32);
+        for event in events {
+            self.string(&event.topic);
+            self.u32(event.attributes.len() as u32);
+            for attribute in &event.attributes {
+                self.string(&attribute.key);
+                self.u32(attribute.value.tag());
+                match &attribute.value {
+                    EventValue::U64(value) => self.u64(*value),
+                    EventValue::Bytes(value) => self.bytes(value),
+                    EventValue::String(value) => self.string(value),
+                }
+            }
+        }
+    }
+}
+
+/// Reads values from a list of field elements, following the layout above.
+pub struct FieldReader<'a, F: FieldCodec> {
+    elements: &'a [F::Element],
+    position: usize,
+    _field: PhantomData<F>,
+}
+
+impl<'a, F: FieldCodec> FieldReader<'a, F> {
+    pub fn new(elements: &'a [F::Element]) -> Self {
+        Self { elements, position: 0, _field: PhantomData }
+    }
+
+    /// Elements which have not been read yet.
+    pub fn remaining(&self) -> &'a [F::Element] {
+        &self.elements[self.position..]
+    }
+
+    fn next_bytes(&mut self, len: usize) -> Result<Vec<u8>, FieldDecodeError> {
+        let element = self.elements.get(self.position).ok_or(FieldDecodeError::UnexpectedEnd)?;
+        let bytes = F::to_be_bytes(element, len).ok_or(FieldDecodeError::OutOfRange(self.position))?;
+        self.position += 1;
+        Ok(bytes)
+    }
+
+    pub fn uint(&mut self, size: usize) -> Result<u64, FieldDecodeError> {
+        let mut bytes = Vec::new();
+        let first = size % F::WORD_BYTES;
+        if first > 0 {
+            bytes.extend(self.next_bytes(first)?);
+        }
+        for _ in 0..size / F::WORD_BYTES {
+            bytes.extend(self.next_bytes(F::WORD_BYTES)?);
+        }
+        Ok(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, FieldDecodeError> {
+        Ok(self.uint(4)? as u32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, FieldDecodeError> {
+        self.uint(8)
+    }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, FieldDecodeError> {
+        let full_words = self.u32()? as usize;
+        let mut data = Vec::new();
+        for _ in 0..full_words {
+            data.extend(self.next_bytes(F::WORD_BYTES)?);
+        }
+        let pending_position = self.position;
+        let pending = self.elements.get(pending_position).ok_or(FieldDecodeError::UnexpectedEnd)?.clone();
+        self.position += 1;
+        let pending_len = self.u32()? as usize;
+        if pending_len >= F::WORD_BYTES {
+            return Err(FieldDecodeError::OutOfRange(self.position - 1));
+        }
+        data.extend(F::to_be_bytes(&pending, pending_len).ok_or(FieldDecodeError::OutOfRange(pending_position))?);
+        Ok(data)
+    }
+
+    pub fn string(&mut self) -> Result<String, FieldDecodeError> {
+        String::from_utf8(self.bytes()?).map_err(|_| FieldDecodeError::InvalidUtf8)
+    }
+
+    pub fn events(&mut self) -> Result<Vec<Event>, FieldDecodeError> {
+        let count = self.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let topic = self.string()?;
+            let attribute_count = self.u32()?;
+            let mut attributes = Vec::new();
+            for _ in 0..attribute_count {
+                let key = self.string()?;
+                let tag_position = self.position;
+                let value = match self.u32()? {
+                    EventValue::U64_TAG => EventValue::U64(self.u64()?),
+                    EventValue::BYTES_TAG => EventValue::Bytes(self.bytes()?),
+                    EventValue::STRING_TAG => EventValue::String(self.string()?),
+                    _ => return Err(FieldDecodeError::OutOfRange(tag_position)),
+                };
+                attributes.push(EventAttribute { key, value });
+            }
+            events.push(Event { topic, attributes });
+        }
+        Ok(events)
+    }
+}
+
+/// Encodes `output` as field elements. `program_outputs` are appended as is.
+/// Outputs older than version 2 are rejected, see the layout above.
+pub fn encode<F: FieldCodec>(output: &HyleOutput<Vec<F::Element>>) -> Result<Vec<F::Element>, EncodeError> {
+    if versions::layout_version(output)? < OutputVersion::V2 {
+        return Err(EncodeError::UnsupportedVersion(output.version));
+    }
+    let mut writer = FieldWriter::<F>::default();
+    writer.u32(output.version);
+    writer.bytes(&output.initial_state);
+    writer.bytes(&output.next_state);
+    writer.string(&output.origin);
+    writer.string(&output.caller);
+    writer.u64(output.block_number);
+    writer.u64(output.block_time);
+    writer.bytes(&output.tx_hash);
+    writer.events(&output.events);
+    writer.elements.extend(output.program_outputs.iter().cloned());
+    Ok(writer.elements)
+}
+
+/// Decodes field elements into an output. The elements left after `events` are the `program_outputs`.
+/// Outputs older than version 2 fail with `LegacyVersion`, see the layout above.
+pub fn decode<F: FieldCodec>(elements: &[F::Element]) -> Result<HyleOutput<Vec<F::Element>>, FieldDecodeError> {
+    let mut reader = FieldReader::<F>::new(elements);
+    let version = reader.u32()?;
+    match OutputVersion::try_from(version) {
+        Ok(OutputVersion::V0 | OutputVersion::V1) => Err(FieldDecodeError::LegacyVersion(version)),
+        Ok(OutputVersion::V2) => decode_v2(version, &mut reader),
+        Err(_) => Err(FieldDecodeError::UnsupportedVersion(version)),
+    }
+}
+
+/// Encodes every output of `batch` with `encode`.
+pub fn encode_batch<F: FieldCodec>(batch: &HyleBatchOutput<Vec<F::Element>>) -> Result<Vec<F::Element>, EncodeError> {
+    let mut writer = FieldWriter::<F>::default();
+    writer.u32(batch.outputs.len() as u32);
+    for output in &batch.outputs {
+        let elements = encode::<F>(output)?;
+        writer.u32(elements.len() as u32);
+        writer.elements.extend(elements);
+    }
+    Ok(writer.elements)
+}
+
+/// Decodes a batch of outputs. The chain of states is not checked here,
+/// see `HyleBatchOutput::check_chain`.
+pub fn decode_batch<F: FieldCodec>(
+    elements: &[F::Element],
+) -> Result<HyleBatchOutput<Vec<F::Element>>, FieldDecodeError> {
+    let mut reader = FieldReader::<F>::new(elements);
+    let count = reader.u32()?;
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        let len = reader.u32()? as usize;
+        let output = reader.remaining().get(..len).ok_or(FieldDecodeError::UnexpectedEnd)?;
+        outputs.push(decode::<F>(output)?);
+        reader.position += len;
+    }
+    Ok(HyleBatchOutput { outputs })
+}
+
+fn decode_v2<F: FieldCodec>(
+    version: u32,
+    reader: &mut FieldReader<F>,
+) -> Result<HyleOutput<Vec<F::Element>>, FieldDecodeError> {
+    Ok(HyleOutput {
+        version,
+        initial_state: reader.bytes()?,
+        next_state: reader.bytes()?,
+        origin: reader.string()?,
+        caller: reader.string()?,
+        block_number: reader.u64()?,
+        block_time: reader.u64()?,
+        tx_hash: reader.bytes()?,
+        events: reader.events()?,
+        program_outputs: reader.remaining().to_vec(),
+    })
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn sample<E>(program_outputs: Vec<E>) -> HyleOutput<Vec<E>> {
+        HyleOutput {
+            version: 2,
+            initial_state: vec![0, 0, 0, 1],
+            next_state: (0..40).collect(),
+            origin: "alice".to_string(),
+            caller: "a caller name longer than thirty-one bytes".to_string(),
+            block_number: 42,
+            block_time: u64::MAX,
+            tx_hash: vec![0xab; 31],
+            events: vec![],
+            program_outputs,
+        }
+    }
+
+    fn felt(value: u64) -> [u8; 32] {
+        felt_from_be_bytes(&value.to_be_bytes())
+    }
+
+    #[test]
+    fn test_stark252_roundtrip() {
+        let output = sample(vec![felt(7)]);
+        let elements = encode::<Stark252>(&output).unwrap();
+        assert_eq!(decode::<Stark252>(&elements).unwrap(), output);
+    }
+
+    #[test]
+    fn test_bn254_roundtrip() {
+        let output = sample(vec![felt(7), felt(8)]);
+        let elements = encode::<Bn254>(&output).unwrap();
+        assert_eq!(decode::<Bn254>(&elements).unwrap(), output);
+    }
+
+    #[test]
+    fn test_goldilocks_roundtrip() {
+        let output = sample(vec![7u64]);
+        let elements = encode::<Goldilocks>(&output).unwrap();
+        assert_eq!(decode::<Goldilocks>(&elements).unwrap(), output);
+    }
+
+    #[test]
+    fn test_legacy_versions() {
+        // Versions 0 and 1 have backend-specific layouts, they are not guessed from this one.
+        let elements = encode::<Stark252>(&sample(vec![felt(7)])).unwrap();
+        for version in [0, 1] {
+            let mut legacy = elements.clone();
+            legacy[0] = felt(version);
+            assert_eq!(decode::<Stark252>(&legacy), Err(FieldDecodeError::LegacyVersion(version as u32)));
+            let output = HyleOutput { version: version as u32, ..sample(vec![7u64]) };
+            assert_eq!(encode::<Goldilocks>(&output), Err(EncodeError::UnsupportedVersion(version as u32)));
+        }
+        assert_eq!(decode::<Goldilocks>(&[3]), Err(FieldDecodeError::UnsupportedVersion(3)));
+    }
+
+    #[test]
+    fn test_events() {
+        let mut output = sample(vec![7u64]);
+        output.events = vec![Event::new("transfer").with("to", "bob").with("amount", u64::MAX).with("memo", vec![1, 2])];
+        let elements = encode::<Goldilocks>(&output).unwrap();
+        assert_eq!(decode::<Goldilocks>(&elements).unwrap(), output);
+    }
+
+    #[test]
+    fn test_batch() {
+        let batch = HyleBatchOutput { outputs: vec![sample(vec![7u64, 8]), sample(vec![9u64])] };
+        let elements = encode_batch::<Goldilocks>(&batch).unwrap();
+        assert_eq!(decode_batch::<Goldilocks>(&elements).unwrap(), batch);
+        assert_eq!(decode_batch::<Goldilocks>(&elements[..elements.len() - 1]), Err(FieldDecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_cairo_byte_array_layout() {
+        // "hello" serializes as [0, 'hello', 5] in Cairo.
+        let mut writer = FieldWriter::<Stark252>::default();
+        writer.string("hello");
+        assert_eq!(writer.elements, vec![felt(0), felt(0x68656c6c6f), felt(5)]);
+
+        let mut writer = FieldWriter::<Goldilocks>::default();
+        writer.string("hello");
+        writer.u64(1 << 32 | 2);
+        assert_eq!(writer.elements, vec![1, 0x68656c6c, 0x6f, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(decode::<Goldilocks>(&[2]), Err(FieldDecodeError::UnexpectedEnd));
+        assert_eq!(decode::<Goldilocks>(&[1 << 32]), Err(FieldDecodeError::OutOfRange(0)));
+        // Pending word longer than its declared length.
+        assert_eq!(FieldReader::<Goldilocks>::new(&[0, 0x100, 1]).bytes(), Err(FieldDecodeError::OutOfRange(1)));
+        // Pending word length must be smaller than a full word.
+        assert_eq!(FieldReader::<Goldilocks>::new(&[0, 0, 4]).bytes(), Err(FieldDecodeError::OutOfRange(2)));
+        assert_eq!(FieldReader::<Stark252>::new(&[felt(0), felt(0xff), felt(1)]).string(), Err(FieldDecodeError::InvalidUtf8));
+        // One event "", one attribute "", then an unknown tag.
+        assert_eq!(
+            FieldReader::<Stark252>::new(&[felt(1), felt(0), felt(0), felt(0), felt(1), felt(0), felt(0), felt(0), felt(9)])
+                .events(),
+            Err(FieldDecodeError::OutOfRange(8))
+        );
+    }
+}
@@ -1,13 +1,34 @@
-#![no_std]
+// `no_std` for zkVM guests. The `schema` feature is host-side only and pulls std in.
+#![cfg_attr(not(feature = "schema"), no_std)]
 
 extern crate alloc;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use alloc::{vec::Vec, string::String};
 
+use events::Event;
+
+pub mod batch;
+pub mod encoding;
+pub mod events;
+pub mod fields;
+pub mod readable;
+#[cfg(feature = "risc0")]
+pub mod risc0;
+#[cfg(feature = "sp1")]
+pub mod sp1;
+pub mod state;
+pub mod validation;
+pub mod versions;
+
+/// Version of the `HyleOutput` protocol fields produced by this crate.
+pub const HYLE_OUTPUT_VERSION: u32 = versions::OutputVersion::CURRENT as u32;
+
 // This is intended as the "typical" input a Hyle smart contract would receive.
 // Note that there is actually no requirement to match this structure.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HyleInput<T> {
     pub initial_state: Vec<u8>,
     pub origin: String,
@@ -18,10 +39,30 @@ pub struct HyleInput<T> {
     pub program_inputs: T
 }
 
+impl<T> HyleInput<T> {
+    /// Builds the output of the transaction, echoing the context fields of the input.
+    pub fn output<U>(&self, next_state: Vec<u8>, events: Vec<Event>, program_outputs: U) -> HyleOutput<U> {
+        HyleOutput {
+            version: HYLE_OUTPUT_VERSION,
+            initial_state: self.initial_state.clone(),
+            next_state,
+            origin: self.origin.clone(),
+            caller: self.caller.clone(),
+            block_number: self.block_number,
+            block_time: self.block_time,
+            tx_hash: self.tx_hash.clone(),
+            events,
+            program_outputs,
+        }
+    }
+}
+
 // This struct should be used as the output (public witness) of a Hyle smart contract.
 // The protocol enforces constraints on the non-generic fields.
 // See the documentation for details.
-#[derive(Serialize, Deserialize, Debug)]
+// Guests should commit it using the canonical encoding, see `encoding`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HyleOutput<T> {
     pub version: u32,
     pub initial_state: Vec<u8>,
@@ -31,5 +72,73 @@ pub struct HyleOutput<T> {
     pub block_number: u64,
     pub block_time: u64,
     pub tx_hash: Vec<u8>,
+    pub events: Vec<Event>,
     pub program_outputs: T
 }
+
+impl<T: AsRef<[u8]>> HyleOutput<T> {
+    /// SHA-256 digest of the canonical encoding of this output.
+    /// Circuits for which public inputs are expensive can expose only this value,
+    /// the full output being provided to the verifier out of band.
+    pub fn commitment(&self) -> Result<[u8; 32], versions::EncodeError> {
+        Ok(Sha256::digest(encoding::encode(self)?).into())
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_commitment() {
+        let mut output = HyleOutput {
+            version: 1,
+            initial_state: vec![0, 0, 0, 1],
+            next_state: vec![0, 0, 0, 2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 1_700_000_000,
+            tx_hash: vec![0xab, 0xcd],
+            events: vec![],
+            program_outputs: vec![7],
+        };
+        let commitment = output.commitment().unwrap();
+        // Golden value, matching the encoding golden vector.
+        assert_eq!(
+            commitment,
+            [
+                0xaf, 0x81, 0xae, 0xaf, 0xcb, 0xd8, 0x3d, 0x38, 0xa9, 0x84, 0x18, 0x1a, 0xac, 0xce, 0x53, 0x2a,
+                0x24, 0xcf, 0xa0, 0xf1, 0x6e, 0xd5, 0xc3, 0x4e, 0x4c, 0xc4, 0xa3, 0xc2, 0x65, 0xe7, 0xa1, 0xae,
+            ]
+        );
+
+        output.block_number += 1;
+        assert_ne!(output.commitment().unwrap(), commitment);
+    }
+
+    #[test]
+    fn test_output_echoes_input_context() {
+        let input = HyleInput {
+            initial_state: vec![1],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 1_700_000_000,
+            tx_hash: vec![0xab],
+            program_inputs: (),
+        };
+        let output = input.output(vec![2], vec![Event::new("transfer")], 7u32);
+        assert_eq!(output.version, HYLE_OUTPUT_VERSION);
+        assert_eq!(output.initial_state, input.initial_state);
+        assert_eq!(output.next_state, vec![2]);
+        assert_eq!((output.origin.as_str(), output.caller.as_str()), ("alice", "bob"));
+        assert_eq!((output.block_number, output.block_time), (42, 1_700_000_000));
+        assert_eq!(output.tx_hash, input.tx_hash);
+        assert_eq!(output.events, vec![Event::new("transfer")]);
+        assert_eq!(output.program_outputs, 7);
+    }
+}
@@ -0,0 +1,262 @@
+// Human-readable JSON representation of `HyleOutput`, opted into by the verifiers.
+//
+// `HyleOutput` itself keeps serializing bytes as arrays of numbers, as zkVM codecs expect.
+// `ReadableOutput` prints `initial_state`, `next_state` and `tx_hash` in the requested
+// `BytesFormat`, and parses any of them: arrays of numbers, hex strings prefixed by `0x`, or
+// base64 strings prefixed by `base64:`. Base64 strings can themselves start with `0x`, the
+// prefix keeps them from being read as hex.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::str::FromStr;
+
+use base64::prelude::*;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::batch::HyleBatchOutput;
+use crate::events::Event;
+use crate::HyleOutput;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BytesFormat {
+    /// Array of numbers, as `HyleOutput` serializes them.
+    #[default]
+    Array,
+    /// Hex string prefixed by `0x`.
+    Hex,
+    /// Base64 string prefixed by `base64:`, standard alphabet with padding.
+    Base64,
+}
+
+/// Prefix of the base64 strings, see `BytesFormat::Base64`.
+pub const BASE64_PREFIX: &str = "base64:";
+
+impl FromStr for BytesFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "array" => Ok(BytesFormat::Array),
+            "hex" => Ok(BytesFormat::Hex),
+            "base64" => Ok(BytesFormat::Base64),
+            _ => Err(alloc::format!("unknown bytes format {:?}, expected array, hex or base64", s)),
+        }
+    }
+}
+
+/// Bytes printed in `format`. Parsing records the format the bytes were found in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadableBytes {
+    pub data: Vec<u8>,
+    pub format: BytesFormat,
+}
+
+impl Serialize for ReadableBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.format {
+            BytesFormat::Array => self.data.serialize(serializer),
+            BytesFormat::Hex => serializer.serialize_str(&alloc::format!("0x{}", hex::encode(&self.data))),
+            BytesFormat::Base64 => {
+                serializer.serialize_str(&alloc::format!("{}{}", BASE64_PREFIX, BASE64_STANDARD.encode(&self.data)))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ReadableBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = ReadableBytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an array of bytes, a 0x-prefixed hex string or a base64:-prefixed base64 string")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut data = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    data.push(byte);
+                }
+                Ok(ReadableBytes { data, format: BytesFormat::Array })
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if let Some(digits) = v.strip_prefix("0x") {
+                    hex::decode(digits)
+                        .map(|data| ReadableBytes { data, format: BytesFormat::Hex })
+                        .map_err(|err| E::custom(alloc::format!("invalid hex bytes: {}", err)))
+                } else if let Some(encoded) = v.strip_prefix(BASE64_PREFIX) {
+                    BASE64_STANDARD
+                        .decode(encoded)
+                        .map(|data| ReadableBytes { data, format: BytesFormat::Base64 })
+                        .map_err(|err| E::custom(alloc::format!("invalid base64 bytes: {}", err)))
+                } else {
+                    Err(E::custom(alloc::format!("bytes string {:?} is not prefixed by 0x or {}", v, BASE64_PREFIX)))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(BytesVisitor)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for ReadableBytes {
+    fn schema_name() -> String {
+        "ReadableBytes".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Metadata, SchemaObject, SubschemaValidation};
+
+        let string = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(alloc::boxed::Box::new(schemars::schema::StringValidation {
+                pattern: Some("^(0x([0-9a-fA-F]{2})*|base64:[A-Za-z0-9+/]*={0,2})$".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        SchemaObject {
+            metadata: Some(alloc::boxed::Box::new(Metadata {
+                description: Some(
+                    "Bytes, as an array of numbers, a 0x-prefixed hex string or a base64:-prefixed base64 string.".into(),
+                ),
+                ..Default::default()
+            })),
+            subschemas: Some(alloc::boxed::Box::new(SubschemaValidation {
+                any_of: Some(alloc::vec![gen.subschema_for::<Vec<u8>>(), string.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// `HyleOutput` with its bytes fields printed as `ReadableBytes`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ReadableOutput<T> {
+    pub version: u32,
+    pub initial_state: ReadableBytes,
+    pub next_state: ReadableBytes,
+    pub origin: String,
+    pub caller: String,
+    pub block_number: u64,
+    pub block_time: u64,
+    pub tx_hash: ReadableBytes,
+    pub events: Vec<Event>,
+    pub program_outputs: T,
+}
+
+impl<T> HyleOutput<T> {
+    /// Converts the output for printing, with its bytes fields in `format`.
+    pub fn readable(self, format: BytesFormat) -> ReadableOutput<T> {
+        let bytes = |data| ReadableBytes { data, format };
+        ReadableOutput {
+            version: self.version,
+            initial_state: bytes(self.initial_state),
+            next_state: bytes(self.next_state),
+            origin: self.origin,
+            caller: self.caller,
+            block_number: self.block_number,
+            block_time: self.block_time,
+            tx_hash: bytes(self.tx_hash),
+            events: self.events,
+            program_outputs: self.program_outputs,
+        }
+    }
+}
+
+impl<T> HyleBatchOutput<T> {
+    /// Converts every output of the batch for printing, see `HyleOutput::readable`.
+    pub fn readable(self, format: BytesFormat) -> Vec<ReadableOutput<T>> {
+        self.outputs.into_iter().map(|output| output.readable(format)).collect()
+    }
+}
+
+impl<T> From<ReadableOutput<T>> for HyleOutput<T> {
+    fn from(output: ReadableOutput<T>) -> Self {
+        HyleOutput {
+            version: output.version,
+            initial_state: output.initial_state.data,
+            next_state: output.next_state.data,
+            origin: output.origin,
+            caller: output.caller,
+            block_number: output.block_number,
+            block_time: output.block_time,
+            tx_hash: output.tx_hash.data,
+            events: output.events,
+            program_outputs: output.program_outputs,
+        }
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn sample() -> HyleOutput<()> {
+        HyleOutput {
+            version: 2,
+            initial_state: vec![0, 0, 0, 1],
+            next_state: vec![0xff],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: (),
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        let json = |format| serde_json::to_value(sample().readable(format)).unwrap();
+        assert_eq!(json(BytesFormat::Array)["initial_state"], serde_json::json!([0, 0, 0, 1]));
+        assert_eq!(json(BytesFormat::Array), serde_json::to_value(sample()).unwrap());
+        assert_eq!(json(BytesFormat::Hex)["initial_state"], "0x00000001");
+        assert_eq!(json(BytesFormat::Hex)["tx_hash"], "0x");
+        assert_eq!(json(BytesFormat::Base64)["initial_state"], "base64:AAAAAQ==");
+        assert_eq!("hex".parse(), Ok(BytesFormat::Hex));
+        assert!("binary".parse::<BytesFormat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_any_format() {
+        for format in [BytesFormat::Array, BytesFormat::Hex, BytesFormat::Base64] {
+            let json = serde_json::to_string(&sample().readable(format)).unwrap();
+            let parsed: ReadableOutput<()> = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.next_state.format, format);
+            assert_eq!(HyleOutput::from(parsed), sample());
+        }
+        // The old array form, as printed from `HyleOutput`.
+        let json = serde_json::to_string(&sample()).unwrap();
+        assert_eq!(HyleOutput::from(serde_json::from_str::<ReadableOutput<()>>(&json).unwrap()), sample());
+        assert!(serde_json::from_str::<ReadableBytes>("\"0xabc\"").is_err());
+        // Unprefixed strings are ambiguous, they are rejected.
+        assert!(serde_json::from_str::<ReadableBytes>("\"AAAAAQ==\"").is_err());
+    }
+
+    #[test]
+    fn test_base64_starting_with_0x() {
+        // Base64 of these bytes starts with `0x`, and the first one, `0xAA`, is also valid hex.
+        for data in [vec![0xd3, 0x10, 0x00], vec![0xd3, 0x1a, 0xbc, 0xde], vec![0xd3, 0x1c, 0xa1]] {
+            assert!(BASE64_STANDARD.encode(&data).starts_with("0x"));
+            for format in [BytesFormat::Array, BytesFormat::Hex, BytesFormat::Base64] {
+                let bytes = ReadableBytes { data: data.clone(), format };
+                let parsed: ReadableBytes = serde_json::from_str(&serde_json::to_string(&bytes).unwrap()).unwrap();
+                assert_eq!(parsed, bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Guest-side helpers for SP1 programs, built with the `sp1` feature.
+
+use sp1_zkvm::io;
+use alloc::vec::Vec;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{batch::HyleBatchOutput, encoding, events::Event, HyleInput, HyleOutput};
+
+/// Reads the transaction input from the guest environment.
+pub fn read_input<T: DeserializeOwned>() -> HyleInput<T> {
+    io::read()
+}
+
+/// Commits `output` to the public values with SP1 serde.
+pub fn commit<U: Serialize>(output: &HyleOutput<U>) {
+    io::commit(output)
+}
+
+/// Commits `output` to the public values using the canonical encoding.
+/// Panics if its version cannot represent all of its fields, see `versions::layout_version`.
+pub fn commit_canonical<U: AsRef<[u8]>>(output: &HyleOutput<U>) {
+    io::commit_slice(&encoding::encode(output).expect("output cannot be encoded"))
+}
+
+/// Commits the outputs of several transactions to the public values using the canonical encoding.
+/// Panics like `commit_canonical`.
+pub fn commit_batch_canonical<U: AsRef<[u8]>>(batch: &HyleBatchOutput<U>) {
+    io::commit_slice(&encoding::encode_batch(batch).expect("batch cannot be encoded"))
+}
+
+/// Reads the input, runs the contract on it and commits the resulting output.
+/// `contract` returns the next state and the program outputs, the context fields are echoed from the input.
+pub fn run<T, U, F>(contract: F)
+where
+    T: DeserializeOwned,
+    U: Serialize,
+    F: FnOnce(&HyleInput<T>) -> (Vec<u8>, U),
+{
+    run_with_events(|input: &HyleInput<T>| {
+        let (next_state, program_outputs) = contract(input);
+        (next_state, Vec::new(), program_outputs)
+    });
+}
+
+/// Same as `run`, for contracts which also emit `events`.
+pub fn run_with_events<T, U, F>(contract: F)
+where
+    T: DeserializeOwned,
+    U: Serialize,
+    F: FnOnce(&HyleInput<T>) -> (Vec<u8>, Vec<Event>, U),
+{
+    let input = read_input::<T>();
+    let (next_state, events, program_outputs) = contract(&input);
+    commit(&input.output(next_state, events, program_outputs));
+}
+
+/// Same as `run_with_events`, committing the output using the canonical encoding.
+pub fn run_canonical<T, U, F>(contract: F)
+where
+    T: DeserializeOwned,
+    U: AsRef<[u8]>,
+    F: FnOnce(&HyleInput<T>) -> (Vec<u8>, Vec<Event>, U),
+{
+    let input = read_input::<T>();
+    let (next_state, events, program_outputs) = contract(&input);
+    commit_canonical(&input.output(next_state, events, program_outputs));
+}
@@ -0,0 +1,272 @@
+// Optional structured state for contracts: a key-value store committed by a Merkle root.
+//
+// A contract using it sets `initial_state` and `next_state` to the roots of its state before and
+// after the transaction. Given the prior state and the `StateDiff` of the transaction, a verifier
+// can then check the transition and report which keys it touched.
+//
+// Merkle tree: leaves are `sha256(0x00 || key_len as u32 LE || key || value)` in key order,
+// nodes are `sha256(0x01 || left || right)`, an odd node is promoted as is to the next level.
+// The root of the empty state is 32 zero bytes.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::HyleOutput;
+
+/// Key-value state. Serialized as a list of `[key, value]` pairs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(from = "Vec<(Vec<u8>, Vec<u8>)>", into = "Vec<(Vec<u8>, Vec<u8>)>")]
+pub struct KvState {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl From<Vec<(Vec<u8>, Vec<u8>)>> for KvState {
+    fn from(entries: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        Self { entries: entries.into_iter().collect() }
+    }
+}
+
+impl From<KvState> for Vec<(Vec<u8>, Vec<u8>)> {
+    fn from(state: KvState) -> Self {
+        state.entries.into_iter().collect()
+    }
+}
+
+impl KvState {
+    pub fn get(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        self.entries.get(key)
+    }
+
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) -> Option<Vec<u8>> {
+        self.entries.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.remove(key)
+    }
+
+    /// Merkle root committing to the whole state.
+    pub fn root(&self) -> [u8; 32] {
+        let mut level: Vec<[u8; 32]> = self
+            .entries
+            .iter()
+            .map(|(key, value)| {
+                let mut hasher = Sha256::new();
+                hasher.update([0u8]);
+                hasher.update((key.len() as u32).to_le_bytes());
+                hasher.update(key);
+                hasher.update(value);
+                hasher.finalize().into()
+            })
+            .collect();
+        if level.is_empty() {
+            return [0; 32];
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => {
+                        let mut hasher = Sha256::new();
+                        hasher.update([1u8]);
+                        hasher.update(left);
+                        hasher.update(right);
+                        hasher.finalize().into()
+                    }
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        level[0]
+    }
+}
+
+/// Change of a single key. `None` means the key is absent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    pub key: Vec<u8>,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+}
+
+/// Changes made to a `KvState` by a transaction. Each key is changed at most once.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub changes: Vec<StateChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The prior value of this key does not match the diff.
+    StaleValue(Vec<u8>),
+    /// The diff changes this key more than once.
+    DuplicateKey(Vec<u8>),
+    InitialRootMismatch,
+    NextRootMismatch,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::StaleValue(key) => write!(f, "prior value of key {:?} does not match the diff", key),
+            StateError::DuplicateKey(key) => write!(f, "key {:?} is changed more than once in the diff", key),
+            StateError::InitialRootMismatch => write!(f, "initial_state is not the root of the prior state"),
+            StateError::NextRootMismatch => write!(f, "next_state is not the root of the updated state"),
+        }
+    }
+}
+
+impl StateDiff {
+    /// Computes the diff turning `prior` into `next`.
+    pub fn between(prior: &KvState, next: &KvState) -> Self {
+        let mut keys: Vec<&Vec<u8>> = prior.entries.keys().chain(next.entries.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        let changes = keys
+            .into_iter()
+            .filter(|key| prior.get(key) != next.get(key))
+            .map(|key| StateChange {
+                key: key.clone(),
+                before: prior.get(key).cloned(),
+                after: next.get(key).cloned(),
+            })
+            .collect();
+        Self { changes }
+    }
+
+    pub fn touched_keys(&self) -> Vec<&Vec<u8>> {
+        self.changes.iter().map(|change| &change.key).collect()
+    }
+
+    /// Checks that the diff was computed against `prior`, and changes each key once.
+    pub fn check(&self, prior: &KvState) -> Result<(), StateError> {
+        let mut keys = BTreeSet::new();
+        if let Some(change) = self.changes.iter().find(|change| !keys.insert(&change.key)) {
+            return Err(StateError::DuplicateKey(change.key.clone()));
+        }
+        match self.changes.iter().find(|change| prior.get(&change.key) != change.before.as_ref()) {
+            Some(change) => Err(StateError::StaleValue(change.key.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the diff against `state`, then applies it.
+    pub fn apply(&self, state: &mut KvState) -> Result<(), StateError> {
+        self.check(state)?;
+        for change in &self.changes {
+            match &change.after {
+                Some(value) => state.insert(change.key.clone(), value.clone()),
+                None => state.remove(&change.key),
+            };
+        }
+        Ok(())
+    }
+
+    /// Checks that the diff turns `prior`, whose root is `initial_state`, into a state whose
+    /// root is `next_state`. Returns the updated state.
+    pub fn check_transition(
+        &self,
+        prior: &KvState,
+        initial_state: &[u8],
+        next_state: &[u8],
+    ) -> Result<KvState, StateError> {
+        if prior.root() != initial_state {
+            return Err(StateError::InitialRootMismatch);
+        }
+        let mut next = prior.clone();
+        self.apply(&mut next)?;
+        if next.root() != next_state {
+            return Err(StateError::NextRootMismatch);
+        }
+        Ok(next)
+    }
+}
+
+impl<T> HyleOutput<T> {
+    /// Checks that this output is the transition of `prior` by `diff`, `initial_state` and
+    /// `next_state` being the state roots. Returns the updated state.
+    pub fn check_state_diff(&self, prior: &KvState, diff: &StateDiff) -> Result<KvState, StateError> {
+        diff.check_transition(prior, &self.initial_state, &self.next_state)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::String, vec};
+
+    fn state(entries: &[(&str, &str)]) -> KvState {
+        entries.iter().map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec())).collect::<Vec<_>>().into()
+    }
+
+    #[test]
+    fn test_root() {
+        assert_eq!(KvState::default().root(), [0; 32]);
+        let a = state(&[("alice", "10"), ("bob", "5"), ("carol", "1")]);
+        // Insertion order does not matter.
+        let b = state(&[("carol", "1"), ("alice", "10"), ("bob", "5")]);
+        assert_eq!(a.root(), b.root());
+        assert_ne!(a.root(), state(&[("alice", "10"), ("bob", "6"), ("carol", "1")]).root());
+        // Key and value boundaries are committed.
+        assert_ne!(state(&[("ab", "c")]).root(), state(&[("a", "bc")]).root());
+    }
+
+    #[test]
+    fn test_diff_apply() {
+        let prior = state(&[("alice", "10"), ("bob", "5")]);
+        let next = state(&[("alice", "7"), ("carol", "3")]);
+        let diff = StateDiff::between(&prior, &next);
+        assert_eq!(diff.touched_keys(), vec![&b"alice".to_vec(), &b"bob".to_vec(), &b"carol".to_vec()]);
+
+        let mut applied = prior.clone();
+        diff.apply(&mut applied).unwrap();
+        assert_eq!(applied, next);
+
+        // The diff does not apply to another prior state.
+        let mut other = state(&[("alice", "11"), ("bob", "5")]);
+        assert_eq!(diff.apply(&mut other), Err(StateError::StaleValue(b"alice".to_vec())));
+
+        // A key changed twice would make the result depend on the order of the changes.
+        let change = |before: &str, after: &str| StateChange {
+            key: b"alice".to_vec(),
+            before: Some(before.as_bytes().to_vec()),
+            after: Some(after.as_bytes().to_vec()),
+        };
+        let diff = StateDiff { changes: vec![change("10", "7"), change("10", "8")] };
+        let mut applied = prior.clone();
+        assert_eq!(diff.apply(&mut applied), Err(StateError::DuplicateKey(b"alice".to_vec())));
+        assert_eq!(applied, prior);
+    }
+
+    #[test]
+    fn test_check_state_diff() {
+        let prior = state(&[("alice", "10"), ("bob", "5")]);
+        let next = state(&[("alice", "5"), ("bob", "10")]);
+        let diff = StateDiff::between(&prior, &next);
+        let mut output = HyleOutput {
+            version: 1,
+            initial_state: prior.root().to_vec(),
+            next_state: next.root().to_vec(),
+            origin: String::new(),
+            caller: String::new(),
+            block_number: 0,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: (),
+        };
+        assert_eq!(output.check_state_diff(&prior, &diff), Ok(next));
+        assert_eq!(output.check_state_diff(&state(&[]), &diff), Err(StateError::InitialRootMismatch));
+        output.next_state = prior.root().to_vec();
+        assert_eq!(output.check_state_diff(&prior, &diff), Err(StateError::NextRootMismatch));
+    }
+}
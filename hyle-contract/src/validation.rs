@@ -0,0 +1,173 @@
+// Checks of a `HyleOutput` against the execution context the caller expects.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::HyleOutput;
+
+/// Expected values for the protocol fields of a `HyleOutput`. Fields left to `None` are not checked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ExpectedContext {
+    pub version: Option<u32>,
+    pub initial_state: Option<Vec<u8>>,
+    pub origin: Option<String>,
+    pub caller: Option<String>,
+    pub min_block_number: Option<u64>,
+    pub max_block_number: Option<u64>,
+    pub min_block_time: Option<u64>,
+    pub max_block_time: Option<u64>,
+    pub tx_hash: Option<Vec<u8>>,
+}
+
+/// A protocol field of the output which does not match the expected context.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum Mismatch {
+    Version { expected: u32, actual: u32 },
+    InitialState { expected: Vec<u8>, actual: Vec<u8> },
+    Origin { expected: String, actual: String },
+    Caller { expected: String, actual: String },
+    BlockNumber { min: Option<u64>, max: Option<u64>, actual: u64 },
+    BlockTime { min: Option<u64>, max: Option<u64>, actual: u64 },
+    TxHash { expected: Vec<u8>, actual: Vec<u8> },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Version { expected, actual } => write!(f, "version: expected {}, got {}", expected, actual),
+            Mismatch::InitialState { expected, actual } => {
+                write!(f, "initial_state: expected {:?}, got {:?}", expected, actual)
+            }
+            Mismatch::Origin { expected, actual } => write!(f, "origin: expected {:?}, got {:?}", expected, actual),
+            Mismatch::Caller { expected, actual } => write!(f, "caller: expected {:?}, got {:?}", expected, actual),
+            Mismatch::BlockNumber { min, max, actual } => {
+                write!(f, "block_number: expected within [{:?}, {:?}], got {}", min, max, actual)
+            }
+            Mismatch::BlockTime { min, max, actual } => {
+                write!(f, "block_time: expected within [{:?}, {:?}], got {}", min, max, actual)
+            }
+            Mismatch::TxHash { expected, actual } => write!(f, "tx_hash: expected {:?}, got {:?}", expected, actual),
+        }
+    }
+}
+
+fn in_window(value: u64, min: Option<u64>, max: Option<u64>) -> bool {
+    !matches!(min, Some(min) if value < min) && !matches!(max, Some(max) if value > max)
+}
+
+impl<T> HyleOutput<T> {
+    /// Checks the protocol fields against `expected`, returning every mismatch found.
+    pub fn validate(&self, expected: &ExpectedContext) -> Result<(), Vec<Mismatch>> {
+        let mut mismatches = Vec::new();
+        if let Some(version) = expected.version {
+            if version != self.version {
+                mismatches.push(Mismatch::Version { expected: version, actual: self.version });
+            }
+        }
+        if let Some(initial_state) = &expected.initial_state {
+            if *initial_state != self.initial_state {
+                mismatches.push(Mismatch::InitialState {
+                    expected: initial_state.clone(),
+                    actual: self.initial_state.clone(),
+                });
+            }
+        }
+        if let Some(origin) = &expected.origin {
+            if *origin != self.origin {
+                mismatches.push(Mismatch::Origin { expected: origin.clone(), actual: self.origin.clone() });
+            }
+        }
+        if let Some(caller) = &expected.caller {
+            if *caller != self.caller {
+                mismatches.push(Mismatch::Caller { expected: caller.clone(), actual: self.caller.clone() });
+            }
+        }
+        if !in_window(self.block_number, expected.min_block_number, expected.max_block_number) {
+            mismatches.push(Mismatch::BlockNumber {
+                min: expected.min_block_number,
+                max: expected.max_block_number,
+                actual: self.block_number,
+            });
+        }
+        if !in_window(self.block_time, expected.min_block_time, expected.max_block_time) {
+            mismatches.push(Mismatch::BlockTime {
+                min: expected.min_block_time,
+                max: expected.max_block_time,
+                actual: self.block_time,
+            });
+        }
+        if let Some(tx_hash) = &expected.tx_hash {
+            if *tx_hash != self.tx_hash {
+                mismatches.push(Mismatch::TxHash { expected: tx_hash.clone(), actual: self.tx_hash.clone() });
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn sample() -> HyleOutput<()> {
+        HyleOutput {
+            version: 1,
+            initial_state: vec![0, 0, 0, 1],
+            next_state: vec![0, 0, 0, 2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 1_700_000_000,
+            tx_hash: vec![0xab, 0xcd],
+            events: vec![],
+            program_outputs: (),
+        }
+    }
+
+    #[test]
+    fn test_validate_matching_context() {
+        assert_eq!(sample().validate(&ExpectedContext::default()), Ok(()));
+
+        let expected = ExpectedContext {
+            version: Some(1),
+            initial_state: Some(vec![0, 0, 0, 1]),
+            origin: Some("alice".to_string()),
+            caller: Some("bob".to_string()),
+            min_block_number: Some(42),
+            max_block_number: Some(42),
+            min_block_time: Some(1_600_000_000),
+            max_block_time: None,
+            tx_hash: Some(vec![0xab, 0xcd]),
+        };
+        assert_eq!(sample().validate(&expected), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_every_mismatch() {
+        let expected = ExpectedContext {
+            version: Some(2),
+            origin: Some("carol".to_string()),
+            max_block_number: Some(41),
+            ..Default::default()
+        };
+        assert_eq!(
+            sample().validate(&expected),
+            Err(vec![
+                Mismatch::Version { expected: 2, actual: 1 },
+                Mismatch::Origin { expected: "carol".to_string(), actual: "alice".to_string() },
+                Mismatch::BlockNumber { min: None, max: Some(41), actual: 42 },
+            ])
+        );
+    }
+}
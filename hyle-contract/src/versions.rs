@@ -0,0 +1,175 @@
+// Versions of the `HyleOutput` protocol fields.
+//
+// Each codec (`encoding`, zkVM serde) has one decoder per version, and older layouts are upgraded
+// to the current `HyleOutput` struct. The field element layout of `fields` starts at `V2`: older
+// field element outputs had a layout of their own per backend (one element per byte for Noir,
+// states as felt text for Cairo), decoded by that backend. Outputs with a version missing from
+// `OutputVersion` are rejected.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::HyleOutput;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum OutputVersion {
+    /// Outputs committed before versions were checked, in the layout of `V1`.
+    V0 = 0,
+    /// Layout without `events`, see `HyleOutputV1`. Field element outputs use the legacy layout
+    /// of their backend.
+    V1 = 1,
+    /// Current layout, adds the `events` log. See `HyleOutput`. Field element outputs use the
+    /// packed layout of `fields`.
+    V2 = 2,
+}
+
+impl OutputVersion {
+    pub const CURRENT: OutputVersion = OutputVersion::V2;
+    pub const SUPPORTED: [OutputVersion; 3] = [OutputVersion::V0, OutputVersion::V1, OutputVersion::V2];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedVersion(pub u32);
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported HyleOutput version {}", self.0)
+    }
+}
+
+impl TryFrom<u32> for OutputVersion {
+    type Error = UnsupportedVersion;
+
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        OutputVersion::SUPPORTED
+            .into_iter()
+            .find(|v| *v as u32 == version)
+            .ok_or(UnsupportedVersion(version))
+    }
+}
+
+/// Error of the encoders, for outputs that the layout of their version cannot represent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    UnsupportedVersion(u32),
+    /// `field` is set, but the layout of `version` has no place for it.
+    UnrepresentableField { version: u32, field: &'static str },
+    /// A field or list is longer than its u32 length prefix can tell.
+    TooLong(usize),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::UnsupportedVersion(v) => write!(f, "unsupported HyleOutput version {}", v),
+            EncodeError::UnrepresentableField { version, field } => {
+                write!(f, "`{}` cannot be encoded in a version {} HyleOutput", field, version)
+            }
+            EncodeError::TooLong(len) => write!(f, "length {} does not fit in a u32 length prefix", len),
+        }
+    }
+}
+
+/// Returns the version of `output`, checking that its layout can carry every field set on it.
+/// Encoders reject such outputs rather than silently dropping the fields.
+pub fn layout_version<T>(output: &HyleOutput<T>) -> Result<OutputVersion, EncodeError> {
+    let version = OutputVersion::try_from(output.version).map_err(|err| EncodeError::UnsupportedVersion(err.0))?;
+    if version < OutputVersion::V2 && !output.events.is_empty() {
+        return Err(EncodeError::UnrepresentableField { version: output.version, field: "events" });
+    }
+    Ok(version)
+}
+
+/// Layout of version 0 and 1 outputs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HyleOutputV1<T> {
+    pub version: u32,
+    pub initial_state: Vec<u8>,
+    pub next_state: Vec<u8>,
+    pub origin: String,
+    pub caller: String,
+    pub block_number: u64,
+    pub block_time: u64,
+    pub tx_hash: Vec<u8>,
+    pub program_outputs: T,
+}
+
+/// Version 0 and 1 outputs carry no events.
+impl<T> From<HyleOutputV1<T>> for HyleOutput<T> {
+    fn from(output: HyleOutputV1<T>) -> Self {
+        HyleOutput {
+            version: output.version,
+            initial_state: output.initial_state,
+            next_state: output.next_state,
+            origin: output.origin,
+            caller: output.caller,
+            block_number: output.block_number,
+            block_time: output.block_time,
+            tx_hash: output.tx_hash,
+            events: Vec::new(),
+            program_outputs: output.program_outputs,
+        }
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_supported_versions() {
+        assert_eq!(OutputVersion::try_from(0), Ok(OutputVersion::V0));
+        assert_eq!(OutputVersion::try_from(1), Ok(OutputVersion::V1));
+        assert_eq!(OutputVersion::try_from(2), Ok(OutputVersion::V2));
+        assert_eq!(OutputVersion::try_from(3), Err(UnsupportedVersion(3)));
+        assert_eq!(OutputVersion::CURRENT as u32, crate::HYLE_OUTPUT_VERSION);
+    }
+
+    #[test]
+    fn test_upgrade_v1() {
+        let output: HyleOutput<()> = HyleOutputV1 {
+            version: 1,
+            initial_state: vec![1],
+            next_state: vec![2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 1_700_000_000,
+            tx_hash: vec![3],
+            program_outputs: (),
+        }
+        .into();
+        assert_eq!((output.version, output.block_number, output.block_time), (1, 42, 1_700_000_000));
+        assert_eq!(output.tx_hash, vec![3]);
+        assert!(output.events.is_empty());
+    }
+
+    #[test]
+    fn test_layout_version() {
+        let mut output: HyleOutput<()> = HyleOutputV1 {
+            version: 1,
+            initial_state: vec![],
+            next_state: vec![],
+            origin: String::new(),
+            caller: String::new(),
+            block_number: 0,
+            block_time: 0,
+            tx_hash: vec![],
+            program_outputs: (),
+        }
+        .into();
+        assert_eq!(layout_version(&output), Ok(OutputVersion::V1));
+        output.events = vec![crate::events::Event::new("transfer")];
+        assert_eq!(layout_version(&output), Err(EncodeError::UnrepresentableField { version: 1, field: "events" }));
+        output.version = 2;
+        assert_eq!(layout_version(&output), Ok(OutputVersion::V2));
+        output.version = 3;
+        assert_eq!(layout_version(&output), Err(EncodeError::UnsupportedVersion(3)));
+    }
+}
@@ -0,0 +1,146 @@
+// `verify-batch`: verifies every proof listed in a manifest, such as proofs stored before an
+// upgrade, and reports the result of each along with a summary.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use verifier_core::checks::read_json;
+use verifier_core::engine::{Engine, JobError};
+use verifier_core::envelope::Status;
+use verifier_core::VerifyError;
+
+use crate::commands::Tools;
+use crate::serve::{handle_value, rejected, Response};
+
+/// Entries are requests of `serve --stdio`, see `serve::Request`. Their relative paths are
+/// relative to the manifest. Entries without `id` are identified by their index.
+#[derive(Deserialize, Debug)]
+pub struct Manifest {
+    pub entries: Vec<Value>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Number of failed entries per error code, see `VerifyError::code`.
+    pub errors: BTreeMap<String, usize>,
+    pub total_ms: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchReport {
+    pub entries: Vec<Response>,
+    pub summary: Summary,
+}
+
+impl BatchReport {
+    fn new(entries: Vec<Response>, total_ms: f64) -> Self {
+        let mut summary = Summary { total: entries.len(), total_ms, ..Default::default() };
+        for entry in &entries {
+            match &entry.envelope.error {
+                None => summary.succeeded += 1,
+                Some(error) => {
+                    summary.failed += 1;
+                    *summary.errors.entry(error.code.clone()).or_default() += 1;
+                }
+            }
+        }
+        BatchReport { entries, summary }
+    }
+
+    /// Error of the first failed entry, if any.
+    pub fn first_error(&self) -> Option<VerifyError> {
+        self.entries.iter().find(|entry| entry.envelope.status == Status::Failure).map(|entry| {
+            let error = entry.envelope.error.as_ref().expect("failures have an error");
+            VerifyError::from_code(&error.code, format!("entry {}: {}", entry.id, error.message))
+        })
+    }
+}
+
+/// Verifies the entries of the manifest at `path` with `engine`. Entries whose verification
+/// panics or times out fail with an internal error, without stopping the others.
+pub fn verify_batch(path: &Path, tools: &Tools, engine: &Engine) -> Result<BatchReport, VerifyError> {
+    let started = Instant::now();
+    let manifest: Manifest = read_json(path)?;
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let entries: Vec<Value> = manifest
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut entry)| {
+            if let Some(fields) = entry.as_object_mut() {
+                fields.entry("id").or_insert(index.into());
+            }
+            entry
+        })
+        .collect();
+    // Kept to report the entries whose job failed.
+    let requests: Vec<(Value, String)> = entries
+        .iter()
+        .map(|entry| (entry["id"].clone(), entry["backend"].as_str().unwrap_or_default().to_string()))
+        .collect();
+
+    let tools = tools.clone();
+    let results = engine.run(entries, move |entry| handle_value(entry, &tools, &base));
+    let entries = results
+        .into_iter()
+        .zip(requests)
+        .map(|(result, (id, backend))| {
+            let failed = |err: JobError| rejected(&backend, VerifyError::Internal(err.to_string()));
+            result.unwrap_or_else(|err| Response { id, envelope: failed(err) })
+        })
+        .collect();
+    Ok(BatchReport::new(entries, started.elapsed().as_secs_f64() * 1000.0))
+}
+
+/// Prints the report of the manifest at `path`. Fails with the error of the first failed entry.
+pub fn run(path: &Path, tools: &Tools, engine: &Engine) -> Result<(), VerifyError> {
+    let report = verify_batch(path, tools, engine)?;
+    println!("{}", serde_json::to_string(&report).expect("reports are serializable"));
+    let summary = &report.summary;
+    eprintln!("{}/{} proofs verified, {} failed", summary.succeeded, summary.total, summary.failed);
+    match report.first_error() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_batch() {
+        let dir = std::env::temp_dir().join(format!("hyle-verify-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest.json");
+        std::fs::write(
+            &manifest,
+            r#"{"entries": [
+                {"backend": "cairo", "proof": "missing.proof"},
+                {"id": "no-inputs", "backend": "miden", "program_id": "ab", "proof": "proof.bin"},
+                "not a request"
+            ]}"#,
+        )
+        .unwrap();
+        let report = verify_batch(&manifest, &Tools::default(), &Engine { concurrency: 2, timeout: None }).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let ids: Vec<&Value> = report.entries.iter().map(|entry| &entry.id).collect();
+        assert_eq!(ids, [&Value::from(0), &Value::from("no-inputs"), &Value::Null]);
+        // The proof is looked up next to the manifest.
+        let message = &report.entries[0].envelope.error.as_ref().unwrap().message;
+        assert!(message.contains(&dir.join("missing.proof").display().to_string()));
+        assert_eq!(report.summary.total, 3);
+        assert_eq!(report.summary.failed, 3);
+        assert_eq!(report.summary.errors, BTreeMap::from([("decode".to_string(), 3)]));
+        assert_eq!(report.first_error(), Some(VerifyError::Decode(format!("entry 0: {}", message))));
+    }
+}
@@ -0,0 +1,332 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use risc0_verifier::program_outputs::ProgramOutputsFormat;
+use risc0_verifier::{ReceiptFormat, ReceiptKind};
+use serde::Deserialize;
+use verifier_core::cache::Cache;
+use verifier_core::checks::OutputArgs;
+use verifier_core::engine::Engine;
+
+#[derive(Parser, Debug)]
+#[command(about = "Verify proofs of any backend supported by Hylé")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    #[command(flatten)]
+    pub tools: Tools,
+}
+
+// Parsed once, the size of the verify arguments does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(flatten)]
+    Verify(Backend),
+    #[clap(about = "Verify requests read from stdin, one JSON per line, or received over HTTP")]
+    Serve(ServeArgs),
+    #[clap(about = "Verify every proof listed in a manifest, and report the result of each")]
+    VerifyBatch(VerifyBatchArgs),
+    #[clap(about = "Remove the entries of the result cache given by --cache-dir")]
+    PruneCache(PruneCacheArgs),
+    #[clap(about = "Print the image id of a RISC Zero guest ELF binary, in hex")]
+    ImageId(ImageIdArgs),
+}
+
+/// Programs run to verify the backends which are not linked in, and the result cache, shared by
+/// all commands.
+#[derive(Args, Debug, Clone)]
+pub struct Tools {
+    /// Path of the sp1-verifier binary.
+    #[arg(long, global = true, default_value = "sp1-verifier")]
+    pub sp1_verifier: PathBuf,
+    /// Path of the noir verifier script, run with bun.
+    #[arg(long, global = true, default_value = "noir-verifier/verifier.ts")]
+    pub noir_verifier: PathBuf,
+    /// Directory of the result cache, see `verifier_core::cache`. Results are not cached without it.
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+    /// Verify proofs even if their result is cached. The new result is still cached.
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+    /// Time after which the sp1-verifier binary and the noir script are killed, set by `verify-batch --timeout`.
+    #[arg(skip)]
+    pub timeout: Option<Duration>,
+}
+
+/// Defaults of the command line flags.
+impl Default for Tools {
+    fn default() -> Self {
+        Tools {
+            sp1_verifier: PathBuf::from("sp1-verifier"),
+            noir_verifier: PathBuf::from("noir-verifier/verifier.ts"),
+            cache_dir: None,
+            no_cache: false,
+            timeout: None,
+        }
+    }
+}
+
+impl Tools {
+    pub fn cache(&self) -> Option<Cache> {
+        self.cache_dir.as_ref().map(Cache::new)
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("mode").required(true).args(["stdio", "http"])))]
+pub struct ServeArgs {
+    /// Read requests from stdin and write results to stdout, see `serve::Request`.
+    #[arg(long)]
+    pub stdio: bool,
+    /// Serve HTTP requests on this address, see `http`.
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+    /// Largest HTTP request body accepted with `--http`, in bytes.
+    #[arg(long, default_value_t = 64 * 1024 * 1024)]
+    pub max_body_size: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyBatchArgs {
+    /// JSON file holding `{"entries": [...]}`, each entry being a request of `serve`, see `batch::Manifest`.
+    pub manifest: PathBuf,
+    /// Number of proofs verified at once. Defaults to the number of CPUs.
+    #[arg(long)]
+    pub jobs: Option<usize>,
+    /// Seconds after which the verification of a proof fails with an internal error. SP1 and Noir
+    /// verifications are killed then. Other backends are verified in-process and cannot be
+    /// stopped: they keep running in the background, and hold their slot of `--jobs` until they end.
+    #[arg(long)]
+    pub timeout: Option<u64>,
+}
+
+impl VerifyBatchArgs {
+    pub fn engine(&self) -> Engine {
+        let default = Engine::default();
+        Engine {
+            concurrency: self.jobs.unwrap_or(default.concurrency),
+            timeout: self.timeout.map(Duration::from_secs),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PruneCacheArgs {
+    /// Only remove the entries cached more than this many seconds ago.
+    #[arg(long)]
+    pub older_than: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImageIdArgs {
+    pub elf: PathBuf,
+}
+
+/// Requests name their backend in a `backend` field, other fields are named like the flags.
+#[derive(Subcommand, Deserialize, Debug)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum Backend {
+    #[clap(about = "Verify a RISC Zero receipt")]
+    Risc0(Risc0Args),
+    #[clap(about = "Verify an SP1 proof, with the sp1-verifier binary")]
+    Sp1(Sp1Args),
+    #[clap(about = "Verify a Miden VM proof")]
+    Miden(MidenArgs),
+    #[clap(about = "Verify a Cairo proof")]
+    Cairo(CairoArgs),
+    #[clap(about = "Verify a Noir proof, with the bun verifier script")]
+    Noir(NoirArgs),
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Risc0(_) => "risc0",
+            Backend::Sp1(_) => "sp1",
+            Backend::Miden(_) => "miden",
+            Backend::Cairo(_) => "cairo",
+            Backend::Noir(_) => "noir",
+        }
+    }
+
+    pub fn program_id(&self) -> Option<String> {
+        match self {
+            Backend::Risc0(args) => Some(args.program_id.clone()),
+            Backend::Sp1(args) => Some(args.program_id.clone()),
+            Backend::Miden(args) => Some(args.program_id.clone()),
+            Backend::Cairo(_) => None,
+            Backend::Noir(args) => Some(args.program_id.display().to_string()),
+        }
+    }
+
+    pub fn proof(&self) -> &Path {
+        match self {
+            Backend::Risc0(args) => &args.proof,
+            Backend::Sp1(args) => &args.proof,
+            Backend::Miden(args) => &args.proof,
+            Backend::Cairo(args) => &args.proof,
+            Backend::Noir(args) => &args.proof,
+        }
+    }
+
+    /// Files the outputs are read from: the proof, then the files given along with it.
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files = vec![self.proof()];
+        match self {
+            Backend::Risc0(args) => {
+                files.extend(args.output.as_deref());
+                files.extend(args.assumptions.iter().map(PathBuf::as_path));
+                files.extend(args.program_outputs_schema.as_deref());
+            }
+            Backend::Sp1(_) => {}
+            Backend::Miden(args) => files.extend([args.inputs.as_path(), args.stack_outputs.as_path()]),
+            Backend::Cairo(_) => {}
+            Backend::Noir(args) => {
+                files.push(&args.program_id);
+                files.extend(args.output.as_deref());
+            }
+        }
+        files
+    }
+
+    /// Makes the relative paths of the arguments relative to `base`, such as the directory of a
+    /// `verify-batch` manifest.
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        let output_args = match self {
+            Backend::Risc0(args) => {
+                resolve(&mut args.proof);
+                args.output.iter_mut().for_each(resolve);
+                args.assumptions.iter_mut().for_each(resolve);
+                args.program_outputs_schema.iter_mut().for_each(resolve);
+                &mut args.output_args
+            }
+            Backend::Sp1(args) => {
+                resolve(&mut args.proof);
+                &mut args.output_args
+            }
+            Backend::Miden(args) => {
+                resolve(&mut args.proof);
+                resolve(&mut args.inputs);
+                resolve(&mut args.stack_outputs);
+                &mut args.output_args
+            }
+            Backend::Cairo(args) => {
+                resolve(&mut args.proof);
+                &mut args.output_args
+            }
+            Backend::Noir(args) => {
+                resolve(&mut args.program_id);
+                resolve(&mut args.proof);
+                args.output.iter_mut().for_each(resolve);
+                &mut args.output_args
+            }
+        };
+        output_args.expected_context.iter_mut().for_each(resolve);
+        output_args.prior_state.iter_mut().for_each(resolve);
+        output_args.state_diff.iter_mut().for_each(resolve);
+        output_args.expect.expect_output.iter_mut().for_each(resolve);
+    }
+
+    pub fn output_args(&self) -> &OutputArgs {
+        match self {
+            Backend::Risc0(args) => &args.output_args,
+            Backend::Sp1(args) => &args.output_args,
+            Backend::Miden(args) => &args.output_args,
+            Backend::Cairo(args) => &args.output_args,
+            Backend::Noir(args) => &args.output_args,
+        }
+    }
+}
+
+#[derive(Args, Deserialize, Debug)]
+pub struct Risc0Args {
+    /// Image id of the guest, as 64 hex digits with or without `0x` prefix, or as a `[u32; 8]` array.
+    #[arg(long)]
+    pub program_id: String,
+    /// Receipt serialized in JSON, bincode or borsh, possibly wrapped in base64 or hex.
+    #[arg(long)]
+    pub proof: PathBuf,
+    /// Serialization of the receipt, detected from its content by default.
+    #[arg(long)]
+    pub receipt_format: Option<ReceiptFormat>,
+    /// Canonically encoded HyleOutput, for receipts whose journal only holds its commitment.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Receipt of an assumption of the guest, a claim of another guest it verified. Can be repeated.
+    #[arg(long = "assumption")]
+    #[serde(default)]
+    pub assumptions: Vec<PathBuf>,
+    /// Kinds of receipt accepted, comma separated. All kinds are accepted by default.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub allowed_receipt_kinds: Vec<ReceiptKind>,
+    /// Decoding of the `program_outputs` following the fields of a risc0 serde journal: raw
+    /// bytes, or a JSON string. They are printed as `null` by default.
+    #[arg(long)]
+    pub program_outputs: Option<ProgramOutputsFormat>,
+    /// JSON file describing the type of the `program_outputs`, see `risc0_verifier::program_outputs::Schema`.
+    #[arg(long, conflicts_with = "program_outputs")]
+    pub program_outputs_schema: Option<PathBuf>,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub output_args: OutputArgs,
+}
+
+#[derive(Args, Deserialize, Debug)]
+pub struct Sp1Args {
+    /// Base64 encoded JSON verification key.
+    #[arg(long)]
+    pub program_id: String,
+    #[arg(long)]
+    pub proof: PathBuf,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub output_args: OutputArgs,
+}
+
+#[derive(Args, Deserialize, Debug)]
+pub struct MidenArgs {
+    /// Hexadecimal representation of the program hash.
+    #[arg(long)]
+    pub program_id: String,
+    #[arg(long)]
+    pub proof: PathBuf,
+    /// JSON file holding the stack inputs.
+    #[arg(long)]
+    pub inputs: PathBuf,
+    /// JSON file holding the stack outputs, which hold the HyleOutput.
+    #[arg(long)]
+    pub stack_outputs: PathBuf,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub output_args: OutputArgs,
+}
+
+#[derive(Args, Deserialize, Debug)]
+pub struct CairoArgs {
+    /// Cairo proofs embed the program in their public inputs, no program id is needed.
+    #[arg(long)]
+    pub proof: PathBuf,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub output_args: OutputArgs,
+}
+
+#[derive(Args, Deserialize, Debug)]
+pub struct NoirArgs {
+    /// Verification key file.
+    #[arg(long)]
+    pub program_id: PathBuf,
+    #[arg(long)]
+    pub proof: PathBuf,
+    /// Canonically encoded HyleOutput, for proofs whose public inputs only hold its commitment.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub output_args: OutputArgs,
+}
@@ -0,0 +1,125 @@
+// Backends verified by running another program, which prints the outputs on stdout like the
+// Rust verifiers do.
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use verifier_core::{ProofOutputs, Verifier, VerifyError};
+use wait_timeout::ChildExt;
+
+/// Reads `pipe` to the end in its own thread, so that the child never blocks on a full pipe.
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Runs `command` and parses the outputs it prints. A failure is reported with its stderr, as
+/// the error matching its exit code, see `VerifyError::exit_code`. The command is killed if it
+/// runs longer than `timeout`.
+fn run(mut command: Command, timeout: Option<Duration>) -> Result<ProofOutputs, VerifyError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let run_error = |err: std::io::Error| VerifyError::Internal(format!("failed to run {}: {}", program, err));
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(run_error)?;
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+    let status = match timeout {
+        None => child.wait().map_err(run_error)?,
+        Some(timeout) => match child.wait_timeout(timeout).map_err(run_error)? {
+            Some(status) => status,
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(VerifyError::Internal(format!("{} killed after {:?}", program, timeout)));
+            }
+        },
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(VerifyError::from_exit_code(status.code(), stderr.trim().to_string()));
+    }
+    let json = serde_json::from_slice(&stdout)
+        .map_err(|err| VerifyError::OutputDecode(format!("failed to parse {} output: {}", program, err)))?;
+    ProofOutputs::from_json(json)
+}
+
+/// Runs the sp1-verifier binary, built outside of the workspace.
+pub struct Sp1Command {
+    pub binary: PathBuf,
+    /// Kills the binary if it runs longer.
+    pub timeout: Option<Duration>,
+}
+
+impl Verifier for Sp1Command {
+    /// Base64 encoded JSON verification key.
+    type ProgramId = String;
+    /// Proof file.
+    type Proof = PathBuf;
+
+    fn verify_outputs(&self, vk: &String, proof: &PathBuf) -> Result<ProofOutputs, VerifyError> {
+        let mut command = Command::new(&self.binary);
+        command.arg(vk).arg(proof);
+        run(command, self.timeout)
+    }
+}
+
+/// Runs the Noir verifier script with bun.
+pub struct NoirCommand {
+    pub script: PathBuf,
+    /// Kills bun if the script runs longer.
+    pub timeout: Option<Duration>,
+}
+
+pub struct NoirProof {
+    pub proof: PathBuf,
+    /// Canonically encoded HyleOutput, for proofs whose public inputs only hold its commitment.
+    pub output: Option<PathBuf>,
+}
+
+impl Verifier for NoirCommand {
+    /// Verification key file.
+    type ProgramId = PathBuf;
+    type Proof = NoirProof;
+
+    fn verify_outputs(&self, vkey: &PathBuf, proof: &NoirProof) -> Result<ProofOutputs, VerifyError> {
+        let mut command = Command::new("bun");
+        command.arg("run").arg(&self.script).arg("--vKeyPath").arg(vkey).arg("--proofPath").arg(&proof.proof);
+        if let Some(output) = &proof.output {
+            command.arg("--outputPath").arg(output);
+        }
+        run(command, self.timeout)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_exit_code() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo bad proof >&2; exit 4");
+        assert_eq!(run(command, None), Err(VerifyError::InvalidProof("bad proof".to_string())));
+    }
+
+    #[test]
+    fn test_kill_on_timeout() {
+        let started = Instant::now();
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        assert!(matches!(run(command, Some(Duration::from_millis(100))), Err(VerifyError::Internal(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,318 @@
+// `serve --http`: local HTTP service verifying the proofs given in request bodies.
+//
+// `POST /verify/{backend}` takes the fields of a `serve --stdio` request, with the content of
+// the files instead of their path, see `HttpRequest`. It answers the outputs, as printed by the
+// CLI, or an error with a status matching its code. `"envelope": true` answers the envelope.
+// `GET /health` and `GET /backends` describe the service.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path as UrlPath, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::prelude::*;
+use hyle_contract::readable::BytesFormat;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use verifier_core::envelope::{Envelope, Status};
+use verifier_core::VerifyError;
+
+use crate::commands::{Backend, Tools};
+use crate::serve::{rejected, verify};
+
+/// Backends served, and whether they are verified in this process, see `external`.
+const BACKENDS: [(&str, bool); 5] = [("risc0", true), ("sp1", false), ("miden", true), ("cairo", true), ("noir", false)];
+
+/// Content of a file given in a request: bytes as a base64 string, or JSON written as is.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum FileContent {
+    Base64(String),
+    Json(Value),
+}
+
+impl FileContent {
+    fn bytes(&self) -> Result<Vec<u8>, VerifyError> {
+        match self {
+            FileContent::Base64(b64) => {
+                BASE64_STANDARD.decode(b64).map_err(|err| VerifyError::Decode(format!("invalid base64 file: {}", err)))
+            }
+            FileContent::Json(json) => Ok(json.to_string().into_bytes()),
+        }
+    }
+}
+
+/// Body of `POST /verify/{backend}`. Fields are those of the backend subcommand.
+#[derive(Deserialize, Debug)]
+pub struct HttpRequest {
+    /// As given on the command line. For Noir, the content of the verification key file.
+    pub program_id: Option<String>,
+    pub proof: FileContent,
+    pub output: Option<FileContent>,
+    pub inputs: Option<FileContent>,
+    pub stack_outputs: Option<FileContent>,
+    #[serde(default)]
+    pub assumptions: Vec<FileContent>,
+    pub expected_context: Option<Value>,
+    pub prior_state: Option<Value>,
+    pub state_diff: Option<Value>,
+    pub expect_version: Option<u32>,
+    pub expect_initial_state: Option<String>,
+    pub expect_origin: Option<String>,
+    pub expect_caller: Option<String>,
+    pub expect_min_block_number: Option<u64>,
+    pub expect_max_block_number: Option<u64>,
+    pub expect_min_block_time: Option<u64>,
+    pub expect_max_block_time: Option<u64>,
+    pub expect_tx_hash: Option<String>,
+    pub expect_output: Option<Value>,
+    /// Kinds of RISC Zero receipt accepted, see `risc0_verifier::ReceiptKind`.
+    pub allowed_receipt_kinds: Option<Value>,
+    pub receipt_format: Option<Value>,
+    pub program_outputs: Option<Value>,
+    pub program_outputs_schema: Option<Value>,
+    pub bytes_format: Option<BytesFormat>,
+    #[serde(default)]
+    pub envelope: bool,
+}
+
+/// Directory holding the files of a request, removed once it is answered.
+struct RequestDir(PathBuf);
+
+impl RequestDir {
+    fn new() -> Result<Self, VerifyError> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let name = format!("hyle-verifier-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&path)
+            .map_err(|err| VerifyError::Internal(format!("failed to create {}: {}", path.display(), err)))?;
+        Ok(RequestDir(path))
+    }
+
+    fn write(&self, name: &str, content: &[u8]) -> Result<String, VerifyError> {
+        let path = self.0.join(name);
+        std::fs::write(&path, content)
+            .map_err(|err| VerifyError::Internal(format!("failed to write {}: {}", path.display(), err)))?;
+        Ok(path.display().to_string())
+    }
+}
+
+impl Drop for RequestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+impl HttpRequest {
+    /// Writes the files of the request to `dir`, and returns the matching subcommand.
+    fn backend(&self, backend: &str, dir: &RequestDir) -> Result<Backend, VerifyError> {
+        let mut fields = Map::new();
+        fields.insert("backend".to_string(), json!(backend));
+        if let Some(program_id) = &self.program_id {
+            let program_id = match backend {
+                "noir" => dir.write("program_id", program_id.as_bytes())?,
+                _ => program_id.clone(),
+            };
+            fields.insert("program_id".to_string(), json!(program_id));
+        }
+        let files = [
+            ("proof", Some(&self.proof)),
+            ("output", self.output.as_ref()),
+            ("inputs", self.inputs.as_ref()),
+            ("stack_outputs", self.stack_outputs.as_ref()),
+        ];
+        for (name, content) in files {
+            if let Some(content) = content {
+                fields.insert(name.to_string(), json!(dir.write(name, &content.bytes()?)?));
+            }
+        }
+        if !self.assumptions.is_empty() {
+            let paths = self
+                .assumptions
+                .iter()
+                .enumerate()
+                .map(|(index, content)| dir.write(&format!("assumption_{}", index), &content.bytes()?))
+                .collect::<Result<Vec<_>, _>>()?;
+            fields.insert("assumptions".to_string(), json!(paths));
+        }
+        let checks = [
+            ("expected_context", &self.expected_context),
+            ("prior_state", &self.prior_state),
+            ("state_diff", &self.state_diff),
+            ("expect_output", &self.expect_output),
+            ("program_outputs_schema", &self.program_outputs_schema),
+        ];
+        for (name, content) in checks {
+            if let Some(content) = content {
+                fields.insert(name.to_string(), json!(dir.write(name, content.to_string().as_bytes())?));
+            }
+        }
+        if let Some(version) = self.expect_version {
+            fields.insert("expect_version".to_string(), json!(version));
+        }
+        let windows = [
+            ("expect_min_block_number", self.expect_min_block_number),
+            ("expect_max_block_number", self.expect_max_block_number),
+            ("expect_min_block_time", self.expect_min_block_time),
+            ("expect_max_block_time", self.expect_max_block_time),
+        ];
+        for (name, value) in windows {
+            if let Some(value) = value {
+                fields.insert(name.to_string(), json!(value));
+            }
+        }
+        let expectations = [
+            ("expect_initial_state", &self.expect_initial_state),
+            ("expect_origin", &self.expect_origin),
+            ("expect_caller", &self.expect_caller),
+            ("expect_tx_hash", &self.expect_tx_hash),
+        ];
+        for (name, value) in expectations {
+            if let Some(value) = value {
+                fields.insert(name.to_string(), json!(value));
+            }
+        }
+        let risc0 = [
+            ("allowed_receipt_kinds", &self.allowed_receipt_kinds),
+            ("receipt_format", &self.receipt_format),
+            ("program_outputs", &self.program_outputs),
+        ];
+        for (name, value) in risc0 {
+            if let Some(value) = value {
+                fields.insert(name.to_string(), value.clone());
+            }
+        }
+        if let Some(bytes_format) = self.bytes_format {
+            fields.insert("bytes_format".to_string(), json!(bytes_format));
+        }
+        serde_json::from_value(Value::Object(fields))
+            .map_err(|err| VerifyError::Decode(format!("invalid {} request: {}", backend, err)))
+    }
+}
+
+fn status_code(envelope: &Envelope) -> StatusCode {
+    match envelope.error.as_ref().map(|error| error.code.as_str()) {
+        None => StatusCode::OK,
+        Some("decode") => StatusCode::BAD_REQUEST,
+        Some("internal") => StatusCode::INTERNAL_SERVER_ERROR,
+        Some(_) => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+/// Verifies the request `body` for `backend`, returning the status and body of the response.
+pub fn handle(backend: &str, body: &[u8], tools: &Tools) -> (StatusCode, Value) {
+    if !BACKENDS.iter().any(|(name, _)| *name == backend) {
+        return (StatusCode::NOT_FOUND, json!({ "error": { "code": "decode", "message": format!("unknown backend {}", backend) } }));
+    }
+    let request = serde_json::from_slice::<HttpRequest>(body)
+        .map_err(|err| VerifyError::Decode(format!("invalid request: {}", err)));
+    let envelope_requested = request.as_ref().is_ok_and(|request| request.envelope);
+    let envelope = match request.and_then(|request| {
+        let dir = RequestDir::new()?;
+        let backend = request.backend(backend, &dir)?;
+        Ok(verify(&backend, tools))
+    }) {
+        Ok(envelope) => envelope,
+        Err(err) => rejected(backend, err),
+    };
+
+    let status = status_code(&envelope);
+    let body = if envelope_requested {
+        serde_json::to_value(&envelope).expect("envelopes are serializable")
+    } else if envelope.status == Status::Success {
+        envelope.output.unwrap_or_default()
+    } else {
+        json!({ "error": envelope.error })
+    };
+    (status, body)
+}
+
+async fn verify_route(State(tools): State<Arc<Tools>>, UrlPath(backend): UrlPath<String>, body: Bytes) -> Response {
+    // Verification is CPU bound, keep it off the async workers.
+    match tokio::task::spawn_blocking(move || handle(&backend, &body, &tools)).await {
+        Ok((status, body)) => (status, Json(body)).into_response(),
+        Err(err) => {
+            let error = json!({ "error": { "code": "internal", "message": err.to_string() } });
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response()
+        }
+    }
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn backends() -> Json<Value> {
+    let backends: Vec<Value> =
+        BACKENDS.iter().map(|(name, in_process)| json!({ "name": name, "in_process": in_process })).collect();
+    Json(Value::Array(backends))
+}
+
+/// Serves requests on `addr` until the process is stopped.
+pub fn serve(addr: SocketAddr, max_body_size: usize, tools: Tools) -> Result<(), VerifyError> {
+    let internal = |err: std::io::Error| VerifyError::Internal(format!("http server failed: {}", err));
+    let runtime = tokio::runtime::Runtime::new().map_err(internal)?;
+    runtime.block_on(async move {
+        let app = Router::new()
+            .route("/health", get(health))
+            .route("/backends", get(backends))
+            .route("/verify/:backend", post(verify_route))
+            .layer(DefaultBodyLimit::max(max_body_size))
+            .with_state(Arc::new(tools));
+        let listener = tokio::net::TcpListener::bind(addr).await.map_err(internal)?;
+        eprintln!("Listening on http://{}", addr);
+        axum::serve(listener, app).await.map_err(internal)
+    })
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_files() {
+        let request: HttpRequest = serde_json::from_value(json!({
+            "program_id": "ab",
+            "proof": "AAEC",
+            "inputs": { "operand_stack": [] },
+            "stack_outputs": { "stack": [], "overflow_addrs": [] },
+            "bytes_format": "hex",
+        }))
+        .unwrap();
+        let dir = RequestDir::new().unwrap();
+        let Backend::Miden(args) = request.backend("miden", &dir).unwrap() else { panic!("expected a miden request") };
+        assert_eq!(args.program_id, "ab");
+        assert_eq!(std::fs::read(&args.proof).unwrap(), vec![0, 1, 2]);
+        assert_eq!(std::fs::read_to_string(&args.inputs).unwrap(), r#"{"operand_stack":[]}"#);
+        assert_eq!(args.output_args.bytes_format, BytesFormat::Hex);
+
+        let path = dir.0.clone();
+        drop(dir);
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_rejected_requests() {
+        let (status, body) = handle("groth16", b"{}", &Tools::default());
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["code"], "decode");
+
+        let (status, body) = handle("risc0", b"not json", &Tools::default());
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "decode");
+
+        // Miden needs its stack inputs and outputs.
+        let (status, body) = handle("miden", br#"{"program_id": "ab", "proof": "AAEC", "envelope": true}"#, &Tools::default());
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["status"], "failure");
+        assert_eq!(body["backend"], "miden");
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use clap::Parser;
+
+use cairo_verifier::verifier::{CairoProof, CairoVerifier};
+use midenvm_verifier::helpers::ProgramHash;
+use midenvm_verifier::{MidenProof, MidenVerifier};
+use risc0_verifier::program_outputs::ProgramOutputs;
+use risc0_verifier::{compute_image_id, parse_image_id, ReceiptKind, Risc0Proof, Risc0Verifier};
+use verifier_core::cache::{self, Cache};
+use verifier_core::envelope::{Phase, Recorder};
+use verifier_core::{exit_with, ProofOutputs, Verifier, VerifyError};
+
+use crate::commands::{Backend, Cli, Command, Tools};
+use crate::external::{NoirCommand, NoirProof, Sp1Command};
+
+mod batch;
+mod commands;
+mod external;
+mod http;
+mod serve;
+
+fn verify(backend: &Backend, tools: &Tools, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    match backend {
+        Backend::Risc0(args) => {
+            let (image_id, proof, program_outputs) = recorder.time(Phase::Decode, || -> Result<_, VerifyError> {
+                let proof = Risc0Proof::read(&args.proof, args.receipt_format, args.output.as_deref(), &args.assumptions)?;
+                let program_outputs =
+                    ProgramOutputs::from_args(args.program_outputs, args.program_outputs_schema.as_deref())?;
+                Ok((parse_image_id(&args.program_id)?, proof, program_outputs))
+            })?;
+            let verifier = Risc0Verifier { allowed_kinds: args.allowed_receipt_kinds.clone(), program_outputs };
+            let (outputs, info) = recorder.time(Phase::Verify, || verifier.verify_receipt(&image_id, &proof))?;
+            recorder.set_proof_info(serde_json::to_value(info).expect("receipt infos are serializable"));
+            Ok(outputs)
+        }
+        Backend::Sp1(args) => {
+            let verifier = Sp1Command { binary: tools.sp1_verifier.clone(), timeout: tools.timeout };
+            recorder.time(Phase::Verify, || verifier.verify_outputs(&args.program_id, &args.proof))
+        }
+        Backend::Miden(args) => {
+            let (program_hash, proof) = recorder.time(Phase::Decode, || -> Result<_, VerifyError> {
+                let program_hash = ProgramHash::read(&args.program_id).map_err(VerifyError::Decode)?;
+                Ok((program_hash, MidenProof::read(&args.proof, &args.inputs, &args.stack_outputs)?))
+            })?;
+            recorder.time(Phase::Verify, || MidenVerifier.verify_outputs(&program_hash, &proof))
+        }
+        Backend::Cairo(args) => {
+            let proof = recorder.time(Phase::Decode, || CairoProof::read(&args.proof))?;
+            recorder.time(Phase::Verify, || CairoVerifier.verify_outputs(&(), &proof))
+        }
+        Backend::Noir(args) => {
+            let verifier = NoirCommand { script: tools.noir_verifier.clone(), timeout: tools.timeout };
+            let proof = NoirProof { proof: args.proof.clone(), output: args.output.clone() };
+            recorder.time(Phase::Verify, || verifier.verify_outputs(&args.program_id, &proof))
+        }
+    }
+}
+
+/// Key of the verification of `backend` in the result cache, if its files can be read.
+fn cache_key(backend: &Backend, tools: &Tools) -> Option<String> {
+    // The Noir verification key is a file, hashed along with the proof rather than its path.
+    // Receipt kinds decide whether a receipt is accepted, and the receipt and program outputs
+    // formats how it is decoded, they are keyed along with the image id. A schema is hashed as a file.
+    let program_id = match backend {
+        Backend::Risc0(args) => {
+            let kinds: Vec<String> = args.allowed_receipt_kinds.iter().map(ReceiptKind::to_string).collect();
+            Some(format!("{} {} {:?} {:?}", args.program_id, kinds.join(","), args.receipt_format, args.program_outputs))
+        }
+        Backend::Noir(_) => None,
+        _ => backend.program_id(),
+    };
+    // The build id covers the backends linked in. SP1 and Noir proofs are verified by other
+    // programs, keyed by their digest, or by their name for a binary found in the PATH.
+    let tool = match backend {
+        Backend::Sp1(_) => Some(&tools.sp1_verifier),
+        Backend::Noir(_) => Some(&tools.noir_verifier),
+        _ => None,
+    };
+    let tool_id = tool.map(|path| cache::file_digest(path).unwrap_or_else(|| path.display().to_string()));
+    let version = format!("{} {}", cache::build_id()?, tool_id.unwrap_or_default());
+    Cache::key(backend.name(), program_id.as_deref(), &backend.files(), &version)
+}
+
+/// Same as `verify`, reading and storing the outputs in the result cache if one is given.
+fn verify_cached(backend: &Backend, tools: &Tools, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    let Some((cache, key)) = tools.cache().zip(cache_key(backend, tools)) else {
+        return verify(backend, tools, recorder);
+    };
+    if !tools.no_cache {
+        if let Some((outputs, proof_info)) = recorder.time(Phase::Decode, || cache.get(&key)) {
+            if let Some(proof_info) = proof_info {
+                recorder.set_proof_info(proof_info);
+            }
+            recorder.set_cached();
+            return Ok(outputs);
+        }
+    }
+    let outputs = verify(backend, tools, recorder)?;
+    // The proof is valid, failing to cache it must not fail its verification.
+    if let Err(err) = cache.put(&key, &outputs, recorder.proof_info()) {
+        eprintln!("{}", err);
+    }
+    Ok(outputs)
+}
+
+/// Verifies a proof and runs the checks requested along with it.
+fn run(backend: &Backend, tools: &Tools, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    let checks = recorder.time(Phase::Decode, || backend.output_args().checks())?;
+    let outputs = verify_cached(backend, tools, recorder)?;
+    checks.run_recorded(&outputs, recorder)?;
+    Ok(outputs)
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match &cli.command {
+        Command::Verify(backend) => {
+            let args = backend.output_args();
+            let recorder = Recorder::new(backend.name(), backend.program_id().as_deref(), backend.proof());
+            recorder.run(args.bytes_format, args.envelope, |recorder| run(backend, &cli.tools, recorder))
+        }
+        Command::Serve(args) => match args.http {
+            Some(addr) => exit_with(|| http::serve(addr, args.max_body_size, cli.tools.clone())),
+            None => exit_with(|| serve::stdio(&cli.tools)),
+        },
+        Command::VerifyBatch(args) => exit_with(|| {
+            let engine = args.engine();
+            let tools = Tools { timeout: engine.timeout, ..cli.tools.clone() };
+            batch::run(&args.manifest, &tools, &engine)
+        }),
+        Command::PruneCache(args) => exit_with(|| {
+            let cache = cli.tools.cache().ok_or(VerifyError::Decode("prune-cache needs --cache-dir".to_string()))?;
+            let stats = cache.prune(args.older_than.map(Duration::from_secs))?;
+            println!("{}", serde_json::to_string(&stats).expect("stats are serializable"));
+            Ok(())
+        }),
+        Command::ImageId(args) => exit_with(|| {
+            println!("{}", hex::encode(compute_image_id(&args.elf)?));
+            Ok(())
+        }),
+    }
+}
@@ -0,0 +1,123 @@
+// `serve --stdio`: verifies requests read from stdin, one JSON per line, and writes the envelope
+// of each result to stdout, one per line. The process stays up between requests, so that the
+// caller does not pay the startup of a verifier for each proof. SP1 and Noir proofs are still
+// verified by running their own verifier, see `external`.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use hyle_contract::readable::BytesFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use verifier_core::envelope::{Envelope, Recorder};
+use verifier_core::VerifyError;
+
+use crate::commands::{Backend, Tools};
+
+/// A verification request: its `backend`, and the flags of the matching subcommand, in snake
+/// case. `id` is echoed in the response, to match them when requests are pipelined.
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub backend: Backend,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Response {
+    pub id: Value,
+    #[serde(flatten)]
+    pub envelope: Envelope,
+}
+
+/// Verifies the proof of `backend` and runs its checks.
+pub fn verify(backend: &Backend, tools: &Tools) -> Envelope {
+    let args = backend.output_args();
+    let recorder = Recorder::new(backend.name(), backend.program_id().as_deref(), backend.proof());
+    recorder.record(args.bytes_format, |recorder| crate::run(backend, tools, recorder))
+}
+
+/// Envelope of a request which could not be verified, for `error`.
+pub fn rejected(backend: &str, error: VerifyError) -> Envelope {
+    Recorder::new(backend, None, Path::new("")).record(BytesFormat::default(), |_| Err(error))
+}
+
+/// Verifies a request given as JSON. Requests which do not parse fail with a decode error.
+pub fn handle(request: &str, tools: &Tools) -> Response {
+    match serde_json::from_str(request) {
+        Ok(request) => handle_value(request, tools, Path::new("")),
+        Err(err) => {
+            Response { id: Value::Null, envelope: rejected("", VerifyError::Decode(format!("invalid request: {}", err))) }
+        }
+    }
+}
+
+/// Same as `handle`, for a parsed request whose relative paths are relative to `base`.
+pub fn handle_value(request: Value, tools: &Tools, base: &Path) -> Response {
+    let id = request["id"].clone();
+    match Request::deserialize(&request) {
+        Ok(mut request) => {
+            request.backend.resolve_paths(base);
+            Response { id, envelope: verify(&request.backend, tools) }
+        }
+        Err(err) => {
+            // Still report the backend of the request, if it can be read.
+            let error = VerifyError::Decode(format!("invalid request: {}", err));
+            Response { id, envelope: rejected(request["backend"].as_str().unwrap_or_default(), error) }
+        }
+    }
+}
+
+/// Serves requests until stdin is closed.
+pub fn stdio(tools: &Tools) -> Result<(), VerifyError> {
+    let io_error = |err: std::io::Error| VerifyError::Internal(format!("stdio failed: {}", err));
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = serde_json::to_string(&handle(&line, tools)).expect("responses are serializable");
+        writeln!(stdout, "{}", response).map_err(io_error)?;
+        stdout.flush().map_err(io_error)?;
+    }
+    Ok(())
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+    use verifier_core::envelope::Status;
+
+    #[test]
+    fn test_parse_request() {
+        let request: Request = serde_json::from_str(
+            r#"{"id": 7, "backend": "miden", "program_id": "ab", "proof": "proof.json",
+                "inputs": "inputs.json", "stack_outputs": "outputs.json", "bytes_format": "hex", "expect_origin": "alice"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id, 7);
+        let Backend::Miden(args) = request.backend else { panic!("expected a miden request") };
+        assert_eq!(args.inputs, PathBuf::from("inputs.json"));
+        assert_eq!(args.output_args.bytes_format, BytesFormat::Hex);
+        assert_eq!(args.output_args.expected_context, None);
+        assert_eq!(args.output_args.expect.expect_origin.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let response = handle(r#"{"id": "a", "backend": "risc0", "proof": "receipt.json"}"#, &Tools::default());
+        assert_eq!(response.id, "a");
+        assert_eq!(response.envelope.status, Status::Failure);
+        assert_eq!(response.envelope.backend, "risc0");
+        assert_eq!(response.envelope.error.unwrap().code, "decode");
+
+        let response = handle("not json", &Tools::default());
+        assert_eq!(response.id, Value::Null);
+        assert_eq!(response.envelope.error.unwrap().code, "decode");
+    }
+}
@@ -358,13 +358,10 @@ impl OutputFile {
 
     /// Converts outputs vectors for stack and overflow addresses to [StackOutputs].
     pub fn stack_outputs(&self) -> Result<StackOutputs, String> {
-        let stack = self.stack.iter().map(|v| v.parse::<u64>().unwrap()).collect::<Vec<u64>>();
+        let parse = |v: &String| v.parse::<u64>().map_err(|err| format!("Invalid stack output {v} - {err}"));
+        let stack = self.stack.iter().map(parse).collect::<Result<Vec<u64>, _>>()?;
 
-        let overflow_addrs = self
-            .overflow_addrs
-            .iter()
-            .map(|v| v.parse::<u64>().unwrap())
-            .collect::<Vec<u64>>();
+        let overflow_addrs = self.overflow_addrs.iter().map(parse).collect::<Result<Vec<u64>, _>>()?;
 
         StackOutputs::try_from_ints(stack, overflow_addrs)
             .map_err(|e| format!("Construct stack outputs failed {e}"))
@@ -0,0 +1,103 @@
+// Verification of Miden VM proofs, see `verifier_core::Verifier`.
+
+// TODO: some of the helpers.rs is currently only used in tests, ignore the warnings for now.
+#![allow(
+    dead_code,
+    unused_imports
+)]
+
+pub mod helpers;
+
+use std::path::Path;
+
+use miden_verifier::{verify, Kernel, ProgramInfo};
+use miden_vm::{Digest, ExecutionProof, StackInputs, StackOutputs};
+
+use crate::helpers::{InputFile, OutputFile, ProofFile};
+use hyle_contract::fields::{self, Goldilocks};
+use hyle_contract::HyleOutput;
+use verifier_core::{to_value, ProofOutputs, Verifier, VerifyError};
+
+pub struct MidenVerifier;
+
+pub struct MidenProof {
+    pub proof: ExecutionProof,
+    pub stack_inputs: StackInputs,
+    /// The program leaves on the stack, top first, the number of elements of its HyleOutput and
+    /// then these elements, see `hyle_contract::fields`. The stack below them is ignored.
+    pub stack_outputs: StackOutputs,
+}
+
+impl MidenProof {
+    /// Reads the proof and its stack inputs and outputs from files.
+    pub fn read(proof_path: &Path, stack_inputs: &Path, stack_outputs: &Path) -> Result<Self, VerifyError> {
+        let input_data = InputFile::read(&Some(stack_inputs.to_path_buf()), proof_path).map_err(VerifyError::Decode)?;
+        let outputs_data = OutputFile::read(&Some(stack_outputs.to_path_buf()), proof_path).map_err(VerifyError::Decode)?;
+        Ok(MidenProof {
+            proof: ProofFile::read(&Some(proof_path.to_path_buf()), proof_path).map_err(VerifyError::Decode)?,
+            stack_inputs: input_data.parse_stack_inputs().map_err(VerifyError::Decode)?,
+            stack_outputs: outputs_data.stack_outputs().map_err(VerifyError::Decode)?,
+        })
+    }
+}
+
+impl Verifier for MidenVerifier {
+    /// Hash of the program.
+    type ProgramId = Digest;
+    type Proof = MidenProof;
+
+    fn verify_outputs(&self, program_hash: &Digest, proof: &MidenProof) -> Result<ProofOutputs, VerifyError> {
+        // This is copied from core midenvm verifier.
+        // TODO accept kernel as CLI argument -- this is not done in core midenVM
+        let kernel = Kernel::default();
+        let program_info = ProgramInfo::new(*program_hash, kernel);
+
+        verify(program_info, proof.stack_inputs.clone(), proof.stack_outputs.clone(), proof.proof.clone())
+            .map_err(|err| VerifyError::InvalidProof(format!("Program failed verification! - {}", err)))?;
+
+        let output = decode_stack(proof.stack_outputs.stack())?;
+        Ok(ProofOutputs::Single(to_value(output)?))
+    }
+}
+
+/// Decodes the HyleOutput left on the stack, see `MidenProof::stack_outputs`. Its length
+/// delimits the program outputs from the rest of the stack, whatever their values.
+fn decode_stack(stack: &[u64]) -> Result<HyleOutput<Vec<u64>>, VerifyError> {
+    let Some((len, stack)) = stack.split_first() else {
+        return Err(VerifyError::OutputDecode("empty stack outputs".to_string()));
+    };
+    let elements = usize::try_from(*len).ok().and_then(|len| stack.get(..len)).ok_or_else(|| {
+        VerifyError::OutputDecode(format!("stack outputs hold {} elements, the output has {}", stack.len(), len))
+    })?;
+    fields::decode::<Goldilocks>(elements)
+        .map_err(|err| VerifyError::OutputDecode(format!("failed to decode stack outputs: {}", err)))
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_stack() {
+        let output = HyleOutput {
+            version: 2,
+            initial_state: vec![1],
+            next_state: vec![2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            // Trailing zeros are program outputs, not padding.
+            program_outputs: vec![7, 0, 0],
+        };
+        let elements = fields::encode::<Goldilocks>(&output).unwrap();
+        let stack = [&[elements.len() as u64][..], &elements, &[0; 4]].concat();
+        assert_eq!(decode_stack(&stack).unwrap(), output);
+        assert!(matches!(decode_stack(&stack[..elements.len()]), Err(VerifyError::OutputDecode(_))));
+        assert!(matches!(decode_stack(&[]), Err(VerifyError::OutputDecode(_))));
+    }
+}
@@ -1,21 +1,99 @@
 import { ProofData, BarretenbergVerifier as Verifier } from '@noir-lang/backend_barretenberg';
 import * as fs from 'fs';
+import { createHash } from 'crypto';
 
 import { parseArgs } from "util";
 
-const { values, positionals } = parseArgs({
-  args: Bun.argv,
-  options: {
-    vKeyPath: {
-      type: 'string',
-    },
-    proofPath: {
-      type: 'string',
-    },
-  },
-  strict: true,
-  allowPositionals: true,
-});
+// Exit codes shared by all verifiers, see `VerifyError::exit_code` in verifier-core/src/lib.rs.
+const EXIT_INTERNAL = 1;
+const EXIT_ARGS = 2;
+const EXIT_DECODE = 3;
+const EXIT_INVALID_PROOF = 4;
+const EXIT_OUTPUT_DECODE = 6;
+const EXIT_POLICY_VIOLATION = 7;
+
+const ERROR_CODES: Record<number, string> = {
+  [EXIT_INTERNAL]: 'internal',
+  [EXIT_DECODE]: 'decode',
+  [EXIT_INVALID_PROOF]: 'invalid_proof',
+  [EXIT_OUTPUT_DECODE]: 'output_decode',
+  [EXIT_POLICY_VIOLATION]: 'policy_violation',
+};
+
+// Invalid arguments exit with EXIT_ARGS, like the argument errors of the other verifiers.
+function parseCli() {
+  try {
+    return parseArgs({
+      args: Bun.argv,
+      options: {
+        vKeyPath: {
+          type: 'string',
+        },
+        proofPath: {
+          type: 'string',
+        },
+        // Canonically encoded HyleOutput, for proofs whose public inputs only hold its commitment.
+        outputPath: {
+          type: 'string',
+        },
+        // JSON file describing the expected execution context, see hyle-contract/src/validation.rs.
+        expectedContext: {
+          type: 'string',
+        },
+        // Values expected in the output, mismatches are reported as a list of `FieldDiff`, see verifier-core/src/checks.rs.
+        // Bytes are given in hex.
+        expectVersion: {
+          type: 'string',
+        },
+        expectInitialState: {
+          type: 'string',
+        },
+        expectOrigin: {
+          type: 'string',
+        },
+        expectCaller: {
+          type: 'string',
+        },
+        expectMinBlockNumber: {
+          type: 'string',
+        },
+        expectMaxBlockNumber: {
+          type: 'string',
+        },
+        expectMinBlockTime: {
+          type: 'string',
+        },
+        expectMaxBlockTime: {
+          type: 'string',
+        },
+        expectTxHash: {
+          type: 'string',
+        },
+        // JSON file holding the output as printed, in any bytes format. Fields left out are not compared.
+        expectOutput: {
+          type: 'string',
+        },
+        // Format of the bytes fields in the printed output: array, hex or base64, see hyle-contract/src/readable.rs.
+        bytesFormat: {
+          type: 'string',
+          default: 'array',
+        },
+        // Print a JSON envelope with the status, error and timings of the verification, see verifier-core/src/envelope.rs.
+        envelope: {
+          type: 'boolean',
+          default: false,
+        },
+      },
+      strict: true,
+      allowPositionals: true,
+    });
+  } catch (err) {
+    process.stderr.write(`${err}\n`);
+    process.exit(EXIT_ARGS);
+  }
+}
+
+const { values, positionals } = parseCli();
 
 interface HyleOutput {
   version: number;
@@ -26,10 +104,81 @@ interface HyleOutput {
   block_number: BigInt;
   block_time: BigInt;
   tx_hash: number[];
+  events: Event[];
+}
+
+// Mirrors hyle-contract/src/events.rs, values are serialized like serde does.
+type EventValue = { u64: BigInt } | { bytes: number[] } | { string: string };
+
+interface Event {
+  topic: string;
+  attributes: { key: string, value: EventValue }[];
 }
 
+const U64_TAG = 0;
+const BYTES_TAG = 1;
+const STRING_TAG = 2;
+
+// Reads an event log given readers for each field type, shared by both layouts.
+function readEvents(u32: () => number, u64: () => BigInt, tag: () => number, bytes: () => number[], str: () => string): Event[] {
+  const events: Event[] = [];
+  const count = u32();
+  for (let i = 0; i < count; i += 1) {
+    const topic = str();
+    const attributes = [];
+    const attributeCount = u32();
+    for (let j = 0; j < attributeCount; j += 1) {
+      const key = str();
+      const valueTag = tag();
+      let value: EventValue;
+      if (valueTag === U64_TAG)
+        value = { u64: u64() };
+      else if (valueTag === BYTES_TAG)
+        value = { bytes: bytes() };
+      else if (valueTag === STRING_TAG)
+        value = { string: str() };
+      else
+        throw new Error(`invalid event value tag ${valueTag}`);
+      attributes.push({ key, value });
+    }
+    events.push({ topic, attributes });
+  }
+  return events;
+}
+
+
+// From version 2, public inputs follow the BN254 layout of hyle-contract/src/fields.rs:
+// integers are single elements, byte arrays are packed 31 bytes per element, Cairo ByteArray style.
+// Older versions use the legacy layout below.
+const WORD_BYTES = 31;
+
+function wordBytes(element: string, length: number): number[] {
+  const value = BigInt(element);
+  const hex = value === 0n ? '' : value.toString(16);
+  if (hex.length > length * 2)
+    throw new Error(`public input ${element} does not fit in ${length} bytes`);
+  return Array.from(Buffer.from(hex.padStart(length * 2, '0'), 'hex'));
+}
+
+function parseArray(vector: string[]): number[] {
+  const fullWords = parseInt(vector.shift() as string);
+  let resp: number[] = [];
+  for (var i = 0; i < fullWords; i += 1)
+    resp.push(...wordBytes(vector.shift() as string, WORD_BYTES));
+  const pendingWord = vector.shift() as string;
+  const pendingWordLen = parseInt(vector.shift() as string);
+  if (pendingWordLen >= WORD_BYTES)
+    throw new Error(`invalid pending word length ${pendingWordLen}`);
+  resp.push(...wordBytes(pendingWord, pendingWordLen));
+  return resp
+}
 
 function parseString(vector: string[]): string {
+  return Buffer.from(parseArray(vector)).toString('utf8');
+}
+
+// Layout of version 0 and 1 public inputs: the length, then one element per byte or character.
+function parseLegacyString(vector: string[]): string {
   let length = parseInt(vector.shift() as string);
   let resp = "";
   for (var i = 0; i < length; i += 1)
@@ -37,7 +186,7 @@ function parseString(vector: string[]): string {
   return resp
 }
 
-function parseArray(vector: string[]): number[] {
+function parseLegacyArray(vector: string[]): number[] {
   let length = parseInt(vector.shift() as string);
   let resp: number[] = [];
   for (var i = 0; i < length; i += 1)
@@ -45,17 +194,36 @@ function parseArray(vector: string[]): number[] {
   return resp
 }
 
+// HyleOutput versions this verifier can decode, see hyle-contract/src/versions.rs.
+// Versions 0 and 1 have no events, and the legacy layout of their byte arrays.
+const SUPPORTED_VERSIONS = [0, 1, 2];
+
+function checkVersion(version: number) {
+  if (!SUPPORTED_VERSIONS.includes(version))
+    throw new Error(`unsupported HyleOutput version ${version}`);
+}
 
 function deserializePublicInputs<T>(publicInputs: string[]): HyleOutput {
   const version = parseInt(publicInputs.shift() as string);
+  checkVersion(version);
+  const array = version < 2 ? parseLegacyArray : parseArray;
+  const str = version < 2 ? parseLegacyString : parseString;
 
-  const initial_state = parseArray(publicInputs);
-  const next_state = parseArray(publicInputs);
-  const origin = parseString(publicInputs);
-  const caller = parseString(publicInputs);
+  const initial_state = array(publicInputs);
+  const next_state = array(publicInputs);
+  const origin = str(publicInputs);
+  const caller = str(publicInputs);
   const block_number = BigInt(publicInputs.shift() as string);
   const block_time = BigInt(publicInputs.shift() as string);
-  const tx_hash = parseArray(publicInputs);
+  const tx_hash = array(publicInputs);
+  const element = () => parseInt(publicInputs.shift() as string);
+  const events = version < 2 ? [] : readEvents(
+    element,
+    () => BigInt(publicInputs.shift() as string),
+    element,
+    () => parseArray(publicInputs),
+    () => parseString(publicInputs),
+  );
   // We don't parse the rest, which correspond to programOutputs
 
   return {
@@ -66,34 +234,292 @@ function deserializePublicInputs<T>(publicInputs: string[]): HyleOutput {
       caller,
       block_number,
       block_time,
-      tx_hash
+      tx_hash,
+      events
   };
 }
 
-const proof = JSON.parse(fs.readFileSync(values.proofPath, { encoding: 'utf8' }));
-const b64vKey = fs.readFileSync(values.vKeyPath, { encoding: 'utf8' });
-const vKey = Uint8Array.from(Buffer.from(b64vKey, 'base64'));
+// Decodes the canonical encoding defined in hyle-contract/src/encoding.rs.
+function decodeCanonical(bytes: Buffer): HyleOutput {
+  let offset = 0;
+  const take = (len: number): Buffer => {
+    if (offset + len > bytes.length)
+      throw new Error("unexpected end of input");
+    const chunk = bytes.subarray(offset, offset + len);
+    offset += len;
+    return chunk;
+  };
+  const u32 = (): number => take(4).readUInt32LE(0);
+  const u64 = (): BigInt => take(8).readBigUInt64LE(0);
+  const bytesField = (): number[] => Array.from(take(u32()));
+  const stringField = (): string => take(u32()).toString('utf8');
 
-const deserializedProofData: ProofData = {
-  proof: Uint8Array.from(proof.proof),
-  publicInputs: proof.publicInputs
-};
+  if (take(4).toString('latin1') !== "HYLE")
+    throw new Error("not a canonical HyleOutput (bad magic)");
+  const encoding = take(1)[0];
+  if (encoding !== 1)
+    throw new Error(`unsupported encoding version ${encoding}`);
+
+  const version = u32();
+  checkVersion(version);
+  const output = {
+    version,
+    initial_state: bytesField(),
+    next_state: bytesField(),
+    origin: stringField(),
+    caller: stringField(),
+    block_number: u64(),
+    block_time: u64(),
+    tx_hash: bytesField(),
+    events: version < 2 ? [] : readEvents(u32, u64, () => take(1)[0], bytesField, stringField),
+  };
+  // program_outputs are opaque bytes, not exposed here.
+  bytesField();
+  if (offset !== bytes.length)
+    throw new Error(`${bytes.length - offset} trailing bytes after output`);
+  return output;
+}
+
+// A 32 bytes commitment does not fit in a single BN254 field element,
+// circuits expose it as two public inputs: the high then the low 16 bytes.
+function commitmentFromPublicInputs(publicInputs: string[]): Buffer {
+  if (publicInputs.length !== 2)
+    throw new Error(`expected 2 public inputs for a commitment, got ${publicInputs.length}`);
+  const half = (input: string) => Buffer.from(BigInt(input).toString(16).padStart(32, '0'), 'hex');
+  return Buffer.concat([half(publicInputs[0]), half(publicInputs[1])]);
+}
+
+interface ExpectedContext {
+  version?: number;
+  initial_state?: number[];
+  origin?: string;
+  caller?: string;
+  min_block_number?: number;
+  max_block_number?: number;
+  min_block_time?: number;
+  max_block_time?: number;
+  tx_hash?: number[];
+}
+
+// Mirrors `HyleOutput::validate`, returning every mismatch as a `FieldDiff`.
+function validate(output: HyleOutput, expected: ExpectedContext): FieldDiff[] {
+  const diffs: FieldDiff[] = [];
+  const sameBytes = (a: number[], b: number[]) => a.length === b.length && a.every((v, i) => v === b[i]);
+  const inWindow = (value: BigInt, min?: number, max?: number) =>
+    (min == null || value >= BigInt(min)) && (max == null || value <= BigInt(max));
+
+  if (expected.version != null && expected.version !== output.version)
+    diffs.push({ field: 'version', expected: expected.version, actual: output.version });
+  if (expected.initial_state != null && !sameBytes(expected.initial_state, output.initial_state))
+    diffs.push({ field: 'initial_state', expected: toHex(expected.initial_state), actual: toHex(output.initial_state) });
+  if (expected.origin != null && expected.origin !== output.origin)
+    diffs.push({ field: 'origin', expected: expected.origin, actual: output.origin });
+  if (expected.caller != null && expected.caller !== output.caller)
+    diffs.push({ field: 'caller', expected: expected.caller, actual: output.caller });
+  if (!inWindow(output.block_number, expected.min_block_number, expected.max_block_number))
+    diffs.push({ field: 'block_number', expected: { min: expected.min_block_number ?? null, max: expected.max_block_number ?? null }, actual: output.block_number });
+  if (!inWindow(output.block_time, expected.min_block_time, expected.max_block_time))
+    diffs.push({ field: 'block_time', expected: { min: expected.min_block_time ?? null, max: expected.max_block_time ?? null }, actual: output.block_time });
+  if (expected.tx_hash != null && !sameBytes(expected.tx_hash, output.tx_hash))
+    diffs.push({ field: 'tx_hash', expected: toHex(expected.tx_hash), actual: toHex(output.tx_hash) });
+  return diffs;
+}
+
+// Mirrors `FieldDiff` in verifier-core/src/checks.rs, bytes are shown in hex.
+interface FieldDiff {
+  field: string;
+  expected: any;
+  actual: any;
+}
+
+const BYTES_FIELDS = ['initial_state', 'next_state', 'tx_hash'];
+
+const toHex = (bytes: number[]) => '0x' + Buffer.from(bytes).toString('hex');
+const BASE64_PREFIX = 'base64:';
+
+// Reads bytes printed in any format, see `ReadableBytes` in hyle-contract/src/readable.rs.
+function parseBytes(value: number[] | string): number[] {
+  if (Array.isArray(value))
+    return value;
+  if (value.startsWith('0x')) {
+    if (!/^([0-9a-fA-F]{2})*$/.test(value.slice(2)))
+      throw new Error(`invalid hex bytes ${JSON.stringify(value)}`);
+    return Array.from(Buffer.from(value.slice(2), 'hex'));
+  }
+  if (value.startsWith(BASE64_PREFIX)) {
+    const encoded = value.slice(BASE64_PREFIX.length);
+    if (!/^[A-Za-z0-9+/]*={0,2}$/.test(encoded) || encoded.length % 4 !== 0)
+      throw new Error(`invalid base64 bytes ${JSON.stringify(value)}`);
+    return Array.from(Buffer.from(encoded, 'base64'));
+  }
+  throw new Error(`bytes string ${JSON.stringify(value)} is not prefixed by 0x or ${BASE64_PREFIX}`);
+}
+
+// Compares the output with the fields of `expected`, a printed output.
+function diffOutput(output: HyleOutput, expected: Record<string, any>): FieldDiff[] {
+  const diffs: FieldDiff[] = [];
+  for (const [field, value] of Object.entries(expected)) {
+    if (!(field in output))
+      throw new Error(`unknown field ${JSON.stringify(field)} in expected output`);
+    let actual = (output as any)[field];
+    let expectedValue = value;
+    if (BYTES_FIELDS.includes(field)) {
+      actual = toHex(actual);
+      expectedValue = toHex(parseBytes(value));
+    }
+    if (stringify(expectedValue) !== stringify(actual))
+      diffs.push({ field, expected: expectedValue, actual });
+  }
+  return diffs;
+}
+
+// bigint in json serialization is a pain in the ass :cry:
+// Disgusting work around -> needs refacto.
+function stringify(value: any): string {
+  var stringified = JSON.stringify(value, (_, v) => typeof v === 'bigint' ? 'BIGINT_' + v.toString() + '_BIGINT' : v);
+  stringified = stringified.replaceAll("\"BIGINT_", "");
+  stringified = stringified.replaceAll("_BIGINT\"", "");
+  return stringified;
+}
+
+const started = performance.now();
+const timings = { decode_ms: 0, verify_ms: 0, checks_ms: 0, total_ms: 0 };
+
+// Mirrors `Envelope` in verifier-core/src/envelope.rs.
+function printEnvelope(output: any, error: { code: string, message: string, diffs?: FieldDiff[] } | null) {
+  let proofDigest = null;
+  try {
+    proofDigest = createHash('sha256').update(fs.readFileSync(values.proofPath)).digest('hex');
+  } catch {}
+  timings.total_ms = performance.now() - started;
+  process.stdout.write(stringify({
+    version: 1,
+    status: error ? 'failure' : 'success',
+    backend: 'noir',
+    program_id: values.vKeyPath ?? null,
+    proof_digest: proofDigest,
+    output,
+    error,
+    timings,
+  }));
+}
+
+function fail(code: number, message: string): never {
+  process.stderr.write(`${message}\n`);
+  if (values.envelope)
+    printEnvelope(null, { code: ERROR_CODES[code], message });
+  process.exit(code);
+}
+
+// Fails with the fields which differ from the expected ones, like `VerifyError::OutputMismatch`.
+function failMismatch(diffs: FieldDiff[]): never {
+  const message = 'output mismatch: ' + diffs
+    .map(diff => `${diff.field}: expected ${stringify(diff.expected)}, got ${stringify(diff.actual)}`)
+    .join(', ');
+  process.stderr.write(`${message}\n`);
+  if (values.envelope)
+    printEnvelope(null, { code: ERROR_CODES[EXIT_POLICY_VIOLATION], message, diffs });
+  process.exit(EXIT_POLICY_VIOLATION);
+}
+
+// Runs `f`, exiting with `code` if it throws.
+function orFail<T>(code: number, f: () => T): T {
+  try {
+    return f();
+  } catch (err) {
+    fail(code, `${err}`);
+  }
+}
+
+const deserializedProofData: ProofData = orFail(EXIT_DECODE, () => {
+  const proof = JSON.parse(fs.readFileSync(values.proofPath, { encoding: 'utf8' }));
+  return {
+    proof: Uint8Array.from(proof.proof),
+    publicInputs: proof.publicInputs
+  };
+});
+const vKey = orFail(EXIT_DECODE, () => {
+  const b64vKey = fs.readFileSync(values.vKeyPath, { encoding: 'utf8' });
+  return Uint8Array.from(Buffer.from(b64vKey, 'base64'));
+});
+
+timings.decode_ms = performance.now() - started;
 
 // Verifying
 const verifier = new Verifier();
-const isValid = await verifier.verifyProof(deserializedProofData, vKey);
+const verifyStarted = performance.now();
+const isValid = await verifier.verifyProof(deserializedProofData, vKey).catch(err => fail(EXIT_INTERNAL, `${err}`));
+timings.verify_ms = performance.now() - verifyStarted;
 if (isValid){
-  const hyleOutput = deserializePublicInputs(deserializedProofData.publicInputs);
+  let hyleOutput: HyleOutput;
+  if (values.outputPath) {
+    const outputBytes = orFail(EXIT_DECODE, () => fs.readFileSync(values.outputPath));
+    hyleOutput = orFail(EXIT_OUTPUT_DECODE, () => decodeCanonical(outputBytes));
+    const commitment = createHash('sha256').update(outputBytes).digest();
+    const expectedCommitment = orFail(EXIT_OUTPUT_DECODE, () => commitmentFromPublicInputs(deserializedProofData.publicInputs));
+    if (!commitment.equals(expectedCommitment))
+      fail(EXIT_OUTPUT_DECODE, "Output file does not match the commitment in the public inputs");
+  } else {
+    hyleOutput = orFail(EXIT_OUTPUT_DECODE, () => deserializePublicInputs(deserializedProofData.publicInputs));
+  }
 
-  // bigint in json serialization is a pain in the ass :cry:
-  // Disgusting work around -> needs refacto.
-  var stringified_output = JSON.stringify(hyleOutput, (_, v) => typeof v === 'bigint' ? 'BIGINT_' + v.toString() + '_BIGINT' : v);
-  stringified_output = stringified_output.replaceAll("\"BIGINT_", "");
-  stringified_output = stringified_output.replaceAll("_BIGINT\"", "");
+  const checksStarted = performance.now();
+  const contextDiffs: FieldDiff[] = [];
+  if (values.expectedContext) {
+    const expected: ExpectedContext = orFail(EXIT_DECODE, () => JSON.parse(fs.readFileSync(values.expectedContext, { encoding: 'utf8' })));
+    contextDiffs.push(...validate(hyleOutput, expected));
+  }
+  const expected: Record<string, any> = {};
+  if (values.expectVersion != null)
+    expected.version = parseInt(values.expectVersion);
+  if (values.expectInitialState != null)
+    expected.initial_state = values.expectInitialState.startsWith('0x') ? values.expectInitialState : '0x' + values.expectInitialState;
+  if (values.expectOrigin != null)
+    expected.origin = values.expectOrigin;
+  if (values.expectCaller != null)
+    expected.caller = values.expectCaller;
+  if (values.expectTxHash != null)
+    expected.tx_hash = values.expectTxHash.startsWith('0x') ? values.expectTxHash : '0x' + values.expectTxHash;
+  const diffs = orFail(EXIT_DECODE, () => {
+    const diffs = [...contextDiffs, ...diffOutput(hyleOutput, expected)];
+    const windows: [string, string | undefined, string | undefined][] = [
+      ['block_number', values.expectMinBlockNumber, values.expectMaxBlockNumber],
+      ['block_time', values.expectMinBlockTime, values.expectMaxBlockTime],
+    ];
+    for (const [field, min, max] of windows) {
+      const actual = BigInt((hyleOutput as any)[field]);
+      if ((min != null && actual < BigInt(min)) || (max != null && actual > BigInt(max)))
+        diffs.push({ field, expected: { min: min == null ? null : Number(min), max: max == null ? null : Number(max) }, actual });
+    }
+    if (values.expectOutput)
+      diffs.push(...diffOutput(hyleOutput, JSON.parse(fs.readFileSync(values.expectOutput, { encoding: 'utf8' }))));
+    return diffs;
+  });
+  if (diffs.length > 0)
+    failMismatch(diffs);
+  timings.checks_ms = performance.now() - checksStarted;
 
-  process.stdout.write(stringified_output);
+  const formatBytes = (bytes: number[]): number[] | string => {
+    switch (values.bytesFormat) {
+      case 'array': return bytes;
+      case 'hex': return toHex(bytes);
+      case 'base64': return BASE64_PREFIX + Buffer.from(bytes).toString('base64');
+      default: throw new Error(`unknown bytes format ${values.bytesFormat}, expected array, hex or base64`);
+    }
+  };
+  const printedOutput = {
+    ...hyleOutput,
+    initial_state: formatBytes(hyleOutput.initial_state),
+    next_state: formatBytes(hyleOutput.next_state),
+    tx_hash: formatBytes(hyleOutput.tx_hash),
+  };
+
+  if (values.envelope)
+    printEnvelope(printedOutput, null);
+  else
+    process.stdout.write(stringify(printedOutput));
   process.exit(0);
 }
 else {
-  process.exit(1);
-}
\ No newline at end of file
+  fail(EXIT_INVALID_PROOF, "Proof failed verification");
+}
@@ -0,0 +1,602 @@
+// Verification of RISC Zero receipts, see `verifier_core::Verifier`.
+
+pub mod program_outputs;
+
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+use bincode::Options;
+use risc0_zkvm::sha::{self, Digest, Digestible};
+use risc0_zkvm::{CompositeReceipt, InnerReceipt, Receipt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use hyle_contract::{
+    encoding,
+    versions::{HyleOutputV1, OutputVersion},
+    HyleOutput,
+};
+use verifier_core::{decode_canonical, decode_committed, to_value, ProofOutputs, Verifier, VerifyError};
+
+use crate::program_outputs::ProgramOutputs;
+
+#[derive(Debug, Clone, Default)]
+pub struct Risc0Verifier {
+    /// Kinds of receipt accepted, an empty list accepts them all.
+    pub allowed_kinds: Vec<ReceiptKind>,
+    /// Decoding of the `program_outputs` of journals committed with risc0 serde.
+    pub program_outputs: ProgramOutputs,
+}
+
+/// Kinds of receipt, from the largest and slowest to verify to the smallest.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    /// One STARK per segment of the execution.
+    Composite,
+    /// A single STARK, recursively proving the segments.
+    Succinct,
+    /// A Groth16 SNARK wrapping a succinct receipt, `InnerReceipt::Compact`.
+    Groth16,
+    /// Receipt without proof, only accepted in dev mode.
+    Fake,
+}
+
+impl ReceiptKind {
+    pub fn of(receipt: &InnerReceipt) -> Self {
+        match receipt {
+            InnerReceipt::Composite(_) => ReceiptKind::Composite,
+            InnerReceipt::Succinct(_) => ReceiptKind::Succinct,
+            InnerReceipt::Compact(_) => ReceiptKind::Groth16,
+            InnerReceipt::Fake { .. } => ReceiptKind::Fake,
+        }
+    }
+}
+
+impl fmt::Display for ReceiptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReceiptKind::Composite => "composite",
+            ReceiptKind::Succinct => "succinct",
+            ReceiptKind::Groth16 => "groth16",
+            ReceiptKind::Fake => "fake",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Description of a verified receipt, reported along with its outputs, see
+/// `verifier_core::envelope::Envelope::proof_info`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptInfo {
+    pub receipt_kind: ReceiptKind,
+    /// Assumptions of the guest resolved by the receipts of other guests, and those of these
+    /// receipts in turn.
+    pub assumptions: Vec<AssumptionInfo>,
+}
+
+/// Claim of another guest, verified by the guest of a receipt with `env::verify`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AssumptionInfo {
+    /// Digest of the claim, as recorded by the guest.
+    pub claim: String,
+    pub image_id: String,
+}
+
+pub struct Risc0Proof {
+    pub receipt: Receipt,
+    /// Canonically encoded output, for receipts whose journal only holds its commitment.
+    pub output: Option<Vec<u8>>,
+    /// Receipts of the assumptions of `receipt` which it does not hold.
+    pub assumptions: Vec<Receipt>,
+}
+
+/// Serialization of a receipt file. When detected, binary receipts can also be wrapped in base64
+/// or hex text; the `bincode-*` formats set the wrapping of bincode receipts.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiptFormat {
+    Json,
+    Bincode,
+    Borsh,
+    /// Bincode, wrapped in hex text, with or without `0x` prefix.
+    BincodeHex,
+    /// Bincode, wrapped in base64 text.
+    BincodeBase64,
+}
+
+/// Decodes the base64 or hex text wrapping a binary receipt, if `content` is such a text.
+/// Only used to detect the format: hex digits are valid base64 too, they are taken as hex.
+fn unwrap_text(content: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(content).ok()?.trim();
+    if text.is_empty() || text.starts_with('{') {
+        return None;
+    }
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    if digits.len() % 2 == 0 && digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return unwrap_hex(content).ok();
+    }
+    unwrap_base64(content).ok()
+}
+
+fn unwrap_hex(content: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(content).map_err(|err| err.to_string())?.trim();
+    hex::decode(text.strip_prefix("0x").unwrap_or(text)).map_err(|err| err.to_string())
+}
+
+fn unwrap_base64(content: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(content).map_err(|err| err.to_string())?.trim();
+    BASE64_STANDARD.decode(text).map_err(|err| err.to_string())
+}
+
+fn decode_as(content: &[u8], format: ReceiptFormat) -> Result<Receipt, String> {
+    match format {
+        ReceiptFormat::Json => serde_json::from_slice(content).map_err(|err| err.to_string()),
+        // Same options as `bincode::deserialize`, but trailing bytes tell another format.
+        ReceiptFormat::Bincode => bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .reject_trailing_bytes()
+            .deserialize(content)
+            .map_err(|err| err.to_string()),
+        ReceiptFormat::Borsh => borsh::from_slice(content).map_err(|err| err.to_string()),
+        ReceiptFormat::BincodeHex => decode_as(&unwrap_hex(content)?, ReceiptFormat::Bincode),
+        ReceiptFormat::BincodeBase64 => decode_as(&unwrap_base64(content)?, ReceiptFormat::Bincode),
+    }
+}
+
+/// Decodes a receipt serialized in `format`, or in the format detected from `content` if `None`.
+pub fn decode_receipt(content: &[u8], format: Option<ReceiptFormat>) -> Result<Receipt, VerifyError> {
+    if let Some(format) = format {
+        return decode_as(content, format)
+            .map_err(|err| VerifyError::Decode(format!("failed to parse receipt as {:?}: {}", format, err)));
+    }
+    let unwrapped = unwrap_text(content);
+    let content = unwrapped.as_deref().unwrap_or(content);
+    if content.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{') {
+        return decode_as(content, ReceiptFormat::Json)
+            .map_err(|err| VerifyError::Decode(format!("failed to parse JSON receipt: {}", err)));
+    }
+    decode_as(content, ReceiptFormat::Bincode).or_else(|bincode_err| {
+        decode_as(content, ReceiptFormat::Borsh).map_err(|borsh_err| {
+            VerifyError::Decode(format!(
+                "failed to parse receipt, as bincode: {}, as borsh: {}",
+                bincode_err, borsh_err
+            ))
+        })
+    })
+}
+
+impl Risc0Proof {
+    /// Reads a receipt serialized in `format`, detected from its content if `None`, the output it
+    /// commits to if given, and the receipts resolving its assumptions, in the same format.
+    pub fn read(
+        receipt_path: &Path,
+        format: Option<ReceiptFormat>,
+        output_path: Option<&Path>,
+        assumption_paths: &[PathBuf],
+    ) -> Result<Self, VerifyError> {
+        let receipt_content = std::fs::read(receipt_path)
+            .map_err(|err| VerifyError::Decode(format!("failed to read receipt file: {}", err)))?;
+        let receipt = decode_receipt(&receipt_content, format)?;
+        let output = match output_path {
+            Some(output_path) => Some(
+                std::fs::read(output_path)
+                    .map_err(|err| VerifyError::Decode(format!("failed to read output file: {}", err)))?,
+            ),
+            None => None,
+        };
+        let assumptions = assumption_paths
+            .iter()
+            .map(|path| {
+                let content = std::fs::read(path).map_err(|err| {
+                    VerifyError::Decode(format!("failed to read assumption receipt {}: {}", path.display(), err))
+                })?;
+                decode_receipt(&content, format)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Risc0Proof { receipt, output, assumptions })
+    }
+}
+
+impl Risc0Verifier {
+    /// Fails if receipts of `kind` are not in `allowed_kinds`.
+    fn check_kind(&self, kind: ReceiptKind) -> Result<(), VerifyError> {
+        if self.allowed_kinds.is_empty() || self.allowed_kinds.contains(&kind) {
+            return Ok(());
+        }
+        let allowed: Vec<String> = self.allowed_kinds.iter().map(ReceiptKind::to_string).collect();
+        Err(VerifyError::PolicyViolation(format!("{} receipts are not allowed, expected {}", kind, allowed.join(" or "))))
+    }
+
+    /// Same as `check_kind`, for a receipt and the receipts of its assumptions, recursively.
+    fn check_kinds(&self, inner: &InnerReceipt) -> Result<(), VerifyError> {
+        self.check_kind(ReceiptKind::of(inner))?;
+        if let InnerReceipt::Composite(composite) = inner {
+            for assumption in &composite.assumptions {
+                self.check_kinds(assumption)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `verify_outputs`, also describing the verified receipt.
+    pub fn verify_receipt(
+        &self,
+        image_id: &[u8; 32],
+        proof: &Risc0Proof,
+    ) -> Result<(ProofOutputs, ReceiptInfo), VerifyError> {
+        let receipt_kind = ReceiptKind::of(&proof.receipt.inner);
+        self.check_kind(receipt_kind)?;
+
+        let receipt = match proof.assumptions.is_empty() {
+            true => Cow::Borrowed(&proof.receipt),
+            false => {
+                let mut receipt = proof.receipt.clone();
+                resolve_assumptions(&mut receipt.inner, &proof.assumptions)?;
+                Cow::Owned(receipt)
+            }
+        };
+        // Assumptions are only as sound as the receipts resolving them.
+        self.check_kinds(&receipt.inner)?;
+
+        // The claim of a receipt starts from the image id of its guest, tell receipts of another
+        // guest apart from invalid ones.
+        if let Ok(claim) = receipt.get_claim() {
+            let receipt_image_id = claim.pre.digest::<sha::Impl>();
+            if receipt_image_id != Digest::from(*image_id) {
+                return Err(VerifyError::KeyMismatch(format!(
+                    "receipt is for image id {}, expected {}",
+                    receipt_image_id,
+                    Digest::from(*image_id)
+                )));
+            }
+        }
+        receipt.verify(*image_id).map_err(|err| VerifyError::InvalidProof(err.to_string()))?;
+        let assumptions = discharged_assumptions(&receipt.inner)?;
+        Ok((decode_journal(proof, &self.program_outputs)?, ReceiptInfo { receipt_kind, assumptions }))
+    }
+}
+
+fn pruned_error(err: impl fmt::Display) -> VerifyError {
+    VerifyError::Decode(format!("failed to read receipt assumptions: {}", err))
+}
+
+/// Digests of the claims assumed by the guest of a composite receipt, from the output of its
+/// last segment.
+fn assumption_claims(receipt: &CompositeReceipt) -> Result<Vec<Digest>, VerifyError> {
+    let Some(segment) = receipt.segments.last() else {
+        return Ok(Vec::new());
+    };
+    let Some(output) = segment.claim.output.as_value().map_err(pruned_error)? else {
+        return Ok(Vec::new());
+    };
+    // Pruned claims keep their digest, which is all receipts are matched by.
+    let assumptions = output.assumptions.as_value().map_err(pruned_error)?;
+    Ok(assumptions.0.iter().map(|assumption| assumption.digest::<sha::Impl>()).collect())
+}
+
+/// Attaches to a composite receipt the receipts of the assumptions it does not hold, found in
+/// `receipts` by the digest of their claim. The attached receipts are resolved in turn.
+fn resolve_assumptions(inner: &mut InnerReceipt, receipts: &[Receipt]) -> Result<(), VerifyError> {
+    let InnerReceipt::Composite(composite) = inner else {
+        return Ok(());
+    };
+    for assumption in composite.assumptions.iter_mut() {
+        resolve_assumptions(assumption, receipts)?;
+    }
+    // Receipts hold the receipts of their first assumptions, in order.
+    for claim in assumption_claims(composite)?.into_iter().skip(composite.assumptions.len()) {
+        let receipt = receipts
+            .iter()
+            .find(|receipt| receipt.get_claim().ok().map(|found| found.digest::<sha::Impl>()) == Some(claim))
+            .ok_or_else(|| VerifyError::InvalidProof(format!("no receipt given for assumption {}", claim)))?;
+        let mut assumption = receipt.inner.clone();
+        resolve_assumptions(&mut assumption, receipts)?;
+        composite.assumptions.push(assumption);
+    }
+    Ok(())
+}
+
+/// Assumptions resolved by the receipts attached to a verified receipt, and by theirs.
+fn discharged_assumptions(inner: &InnerReceipt) -> Result<Vec<AssumptionInfo>, VerifyError> {
+    let InnerReceipt::Composite(composite) = inner else {
+        return Ok(Vec::new());
+    };
+    let mut assumptions = Vec::new();
+    for assumption in &composite.assumptions {
+        let claim = assumption.get_claim().map_err(|err| VerifyError::Internal(err.to_string()))?;
+        assumptions.push(AssumptionInfo {
+            claim: claim.digest::<sha::Impl>().to_string(),
+            image_id: claim.pre.digest::<sha::Impl>().to_string(),
+        });
+        assumptions.extend(discharged_assumptions(assumption)?);
+    }
+    Ok(assumptions)
+}
+
+fn decode_journal(proof: &Risc0Proof, program_outputs: &ProgramOutputs) -> Result<ProofOutputs, VerifyError> {
+    // Guests committing the canonical encoding are decoded with it, others with risc0 serde.
+    let journal = &proof.receipt.journal.bytes;
+    if let Some(output) = &proof.output {
+        return decode_committed(journal, output);
+    }
+    if encoding::is_canonical(journal) || encoding::is_canonical_batch(journal) {
+        return decode_canonical(journal);
+    }
+    if journal.len() % 4 != 0 {
+        return Err(VerifyError::OutputDecode(format!("journal of {} bytes is not a sequence of words", journal.len())));
+    }
+    let words: Vec<u32> =
+        journal.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect();
+    // The version is the first word of the journal, it selects the layout to decode.
+    let version = *words.first().ok_or_else(|| VerifyError::OutputDecode("empty journal".to_string()))?;
+    let (output, len): (HyleOutput<()>, usize) = match OutputVersion::try_from(version) {
+        Ok(OutputVersion::V0 | OutputVersion::V1) => {
+            decode_prefix::<HyleOutputV1<()>>(&words).map(|(output, len)| (output.into(), len))?
+        }
+        Ok(OutputVersion::V2) => decode_prefix(&words)?,
+        Err(err) => return Err(VerifyError::OutputDecode(err.to_string())),
+    };
+    let mut output = to_value(output)?;
+    output.program_outputs = program_outputs.decode(&words[len..])?;
+    Ok(ProofOutputs::Single(output))
+}
+
+/// Decodes the protocol fields at the start of `words`, and the number of words they take.
+/// `()` takes no words, the program outputs follow them.
+fn decode_prefix<T: Serialize + DeserializeOwned>(words: &[u32]) -> Result<(T, usize), VerifyError> {
+    let decode_error = |err: risc0_zkvm::serde::Error| VerifyError::OutputDecode(err.to_string());
+    let prefix: T = risc0_zkvm::serde::from_slice(words).map_err(decode_error)?;
+    let len = risc0_zkvm::serde::to_vec(&prefix).map_err(decode_error)?.len();
+    Ok((prefix, len))
+}
+
+impl Verifier for Risc0Verifier {
+    /// Image id of the guest.
+    type ProgramId = [u8; 32];
+    type Proof = Risc0Proof;
+
+    fn verify_outputs(&self, image_id: &[u8; 32], proof: &Risc0Proof) -> Result<ProofOutputs, VerifyError> {
+        self.verify_receipt(image_id, proof).map(|(outputs, _)| outputs)
+    }
+}
+
+/// Parses an image id given as 64 hex digits, with or without `0x` prefix, or as the `[u32; 8]`
+/// array of words generated for guest methods. Shorter hex ids are rejected rather than padded,
+/// so that a truncated id is not taken for another one.
+pub fn parse_image_id(image_id: &str) -> Result<[u8; 32], VerifyError> {
+    let invalid = |reason: String| VerifyError::Decode(format!("invalid image id {:?}: {}", image_id, reason));
+    let image_id = image_id.trim();
+    if let Some(words) = image_id.strip_prefix('[') {
+        let words = words.strip_suffix(']').ok_or_else(|| invalid("missing closing bracket".to_string()))?;
+        let words = words
+            .split(',')
+            .map(|word| word.trim().parse::<u32>().map_err(|err| invalid(format!("invalid word {:?}: {}", word.trim(), err))))
+            .collect::<Result<Vec<u32>, _>>()?;
+        let words: [u32; 8] =
+            words.try_into().map_err(|words: Vec<u32>| invalid(format!("{} words, expected 8", words.len())))?;
+        // Words are little-endian, as in `risc0_zkvm::sha::Digest`.
+        let mut bytes = [0; 32];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        return Ok(bytes);
+    }
+    let digits = image_id.strip_prefix("0x").unwrap_or(image_id);
+    if digits.is_empty() {
+        return Err(invalid("empty".to_string()));
+    }
+    if let Some((position, digit)) = digits.char_indices().find(|(_, digit)| !digit.is_ascii_hexdigit()) {
+        return Err(invalid(format!("invalid hex digit {:?} at position {}", digit, position)));
+    }
+    if digits.len() != 64 {
+        return Err(invalid(format!("{} hex digits, expected 64", digits.len())));
+    }
+    let mut bytes = [0; 32];
+    hex::decode_to_slice(digits, &mut bytes).map_err(|err| invalid(err.to_string()))?;
+    Ok(bytes)
+}
+
+/// Computes the image id of the guest ELF binary at `elf_path`.
+pub fn compute_image_id(elf_path: &Path) -> Result<[u8; 32], VerifyError> {
+    let elf = std::fs::read(elf_path)
+        .map_err(|err| VerifyError::Decode(format!("failed to read {}: {}", elf_path.display(), err)))?;
+    let image_id = risc0_zkvm::compute_image_id(&elf)
+        .map_err(|err| VerifyError::Decode(format!("invalid guest ELF {}: {}", elf_path.display(), err)))?;
+    Ok(image_id.as_bytes().try_into().expect("image ids are 32 bytes"))
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use risc0_zkvm::{Assumptions, MaybePruned, Output, ReceiptClaim, SegmentReceipt};
+
+    const IMAGE_ID: [u8; 32] = [1; 32];
+
+    /// Receipt without proof, enough to check what is read before verifying.
+    fn fake_receipt(image_id: [u8; 32], journal: Vec<u8>) -> Receipt {
+        Receipt::new(InnerReceipt::Fake { claim: ReceiptClaim::ok(Digest::from(image_id), journal.clone()) }, journal)
+    }
+
+    fn fake_proof(image_id: [u8; 32]) -> Risc0Proof {
+        Risc0Proof { receipt: fake_receipt(image_id, vec![0; 4]), output: None, assumptions: vec![] }
+    }
+
+    #[test]
+    fn test_allowed_kinds() {
+        let kinds = [ReceiptKind::Composite, ReceiptKind::Succinct, ReceiptKind::Groth16, ReceiptKind::Fake];
+        let verifier = Risc0Verifier::default();
+        for kind in kinds {
+            assert_eq!(verifier.check_kind(kind), Ok(()));
+        }
+
+        let verifier = Risc0Verifier {
+            allowed_kinds: vec![ReceiptKind::Succinct, ReceiptKind::Groth16],
+            ..Default::default()
+        };
+        assert_eq!(verifier.check_kind(ReceiptKind::Groth16), Ok(()));
+        let err = verifier.verify_receipt(&IMAGE_ID, &fake_proof(IMAGE_ID)).unwrap_err();
+        assert_eq!(
+            err,
+            VerifyError::PolicyViolation("fake receipts are not allowed, expected succinct or groth16".to_string())
+        );
+        assert_eq!(err.exit_code(), 7);
+    }
+
+    #[test]
+    fn test_key_mismatch() {
+        // The image id is compared before verifying, fake receipts need no dev mode.
+        let err = Risc0Verifier::default().verify_receipt(&IMAGE_ID, &fake_proof([2; 32])).unwrap_err();
+        assert!(matches!(&err, VerifyError::KeyMismatch(message) if message.contains(&hex::encode([2; 32]))));
+        assert_eq!(err.exit_code(), 5);
+    }
+
+    #[test]
+    fn test_unwrap_text() {
+        let bytes = vec![1, 0, 0, 0, 0xff];
+        assert_eq!(unwrap_text(b"01000000ff\n"), Some(bytes.clone()));
+        assert_eq!(unwrap_text(b"0x01000000ff"), Some(bytes.clone()));
+        assert_eq!(unwrap_text(BASE64_STANDARD.encode(&bytes).as_bytes()), Some(bytes.clone()));
+        // JSON and raw binary receipts are not wrapped.
+        assert_eq!(unwrap_text(b" {\"inner\": {}}"), None);
+        assert_eq!(unwrap_text(&bytes), None);
+        assert_eq!(unwrap_text(&[1, 0, 0, 0]), None);
+
+        // "abcd" is valid hex and valid base64: detection takes it as hex, the explicit
+        // wrappings decode it as told.
+        assert_eq!(unwrap_text(b"abcd"), Some(vec![0xab, 0xcd]));
+        assert_eq!(unwrap_hex(b"abcd"), Ok(vec![0xab, 0xcd]));
+        assert_eq!(unwrap_base64(b"abcd"), Ok(vec![0x69, 0xb7, 0x1d]));
+    }
+
+    /// Composite receipt of a guest which assumed `claims`, without their receipts.
+    fn composite_receipt(claims: Vec<ReceiptClaim>) -> InnerReceipt {
+        let journal = vec![0; 4];
+        let mut claim = ReceiptClaim::ok(Digest::from(IMAGE_ID), journal.clone());
+        claim.output = MaybePruned::Value(Some(Output {
+            journal: MaybePruned::Value(journal),
+            assumptions: MaybePruned::Value(Assumptions(claims.into_iter().map(MaybePruned::Value).collect())),
+        }));
+        let segment = SegmentReceipt { seal: vec![], index: 0, hashfn: "poseidon2".to_string(), claim };
+        InnerReceipt::Composite(CompositeReceipt { segments: vec![segment], assumptions: vec![], journal_digest: None })
+    }
+
+    #[test]
+    fn test_resolve_assumptions() {
+        let assumed = fake_receipt([2; 32], vec![5; 4]);
+        let claim = assumed.get_claim().unwrap();
+        // Receipts given with `--assumption`, other receipts are ignored.
+        let dir = std::env::temp_dir().join(format!("risc0-verifier-assumptions-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("other.json"), dir.join("assumed.bin")];
+        std::fs::write(&paths[0], serde_json::to_vec(&fake_receipt([3; 32], vec![])).unwrap()).unwrap();
+        std::fs::write(&paths[1], bincode::serialize(&assumed).unwrap()).unwrap();
+        std::fs::write(dir.join("receipt.json"), serde_json::to_vec(&fake_receipt(IMAGE_ID, vec![])).unwrap()).unwrap();
+        let proof = Risc0Proof::read(&dir.join("receipt.json"), None, None, &paths).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut inner = composite_receipt(vec![claim.clone()]);
+        resolve_assumptions(&mut inner, &proof.assumptions).unwrap();
+        let InnerReceipt::Composite(composite) = &inner else { unreachable!() };
+        assert_eq!(composite.assumptions.len(), 1);
+        assert_eq!(ReceiptKind::of(&composite.assumptions[0]), ReceiptKind::Fake);
+        assert_eq!(
+            discharged_assumptions(&inner).unwrap(),
+            vec![AssumptionInfo {
+                claim: claim.digest::<sha::Impl>().to_string(),
+                image_id: Digest::from([2; 32]).to_string(),
+            }]
+        );
+        // Assumptions already held are not resolved again.
+        resolve_assumptions(&mut inner, &[]).unwrap();
+
+        // The allowed kinds apply to the receipts of the assumptions too.
+        let verifier = Risc0Verifier { allowed_kinds: vec![ReceiptKind::Composite], ..Default::default() };
+        assert_eq!(verifier.check_kind(ReceiptKind::of(&inner)), Ok(()));
+        assert_eq!(
+            verifier.check_kinds(&inner),
+            Err(VerifyError::PolicyViolation("fake receipts are not allowed, expected composite".to_string()))
+        );
+        let verifier = Risc0Verifier { allowed_kinds: vec![ReceiptKind::Composite, ReceiptKind::Fake], ..Default::default() };
+        assert_eq!(verifier.check_kinds(&inner), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_assumption() {
+        let claim = fake_receipt([2; 32], vec![5; 4]).get_claim().unwrap();
+        let mut inner = composite_receipt(vec![claim.clone()]);
+        let err = resolve_assumptions(&mut inner, &[fake_receipt([3; 32], vec![])]).unwrap_err();
+        assert_eq!(
+            err,
+            VerifyError::InvalidProof(format!("no receipt given for assumption {}", claim.digest::<sha::Impl>()))
+        );
+    }
+
+    #[test]
+    fn test_decode_receipt() {
+        let receipt = fake_receipt(IMAGE_ID, vec![1, 2, 3, 4]);
+        let json = serde_json::to_vec(&receipt).unwrap();
+        let bincode = bincode::serialize(&receipt).unwrap();
+        let borsh = borsh::to_vec(&receipt).unwrap();
+        let base64_text = BASE64_STANDARD.encode(&bincode).into_bytes();
+        let hex_text = format!("0x{}\n", hex::encode(&bincode)).into_bytes();
+        let contents = [
+            (json.clone(), ReceiptFormat::Json),
+            (bincode.clone(), ReceiptFormat::Bincode),
+            (borsh.clone(), ReceiptFormat::Borsh),
+            (base64_text.clone(), ReceiptFormat::BincodeBase64),
+            (hex_text.clone(), ReceiptFormat::BincodeHex),
+        ];
+        for (content, format) in contents {
+            for format in [None, Some(format)] {
+                let decoded = decode_receipt(&content, format).unwrap();
+                assert_eq!(decoded.journal.bytes, receipt.journal.bytes);
+                assert_eq!(ReceiptKind::of(&decoded.inner), ReceiptKind::Fake);
+                assert_eq!(
+                    decoded.get_claim().unwrap().digest::<sha::Impl>(),
+                    receipt.get_claim().unwrap().digest::<sha::Impl>()
+                );
+            }
+        }
+        assert!(decode_receipt(BASE64_STANDARD.encode(&borsh).as_bytes(), None).is_ok());
+        // An explicit format does not unwrap text, nor guess the wrapping.
+        assert!(matches!(decode_receipt(&hex_text, Some(ReceiptFormat::Bincode)), Err(VerifyError::Decode(_))));
+        assert!(matches!(decode_receipt(&hex_text, Some(ReceiptFormat::BincodeBase64)), Err(VerifyError::Decode(_))));
+        assert!(matches!(decode_receipt(&base64_text, Some(ReceiptFormat::BincodeHex)), Err(VerifyError::Decode(_))));
+        assert!(matches!(decode_receipt(&borsh, Some(ReceiptFormat::Bincode)), Err(VerifyError::Decode(_))));
+        assert!(matches!(decode_receipt(&bincode, Some(ReceiptFormat::Json)), Err(VerifyError::Decode(_))));
+        let trailing = [&bincode[..], &[0]].concat();
+        assert!(matches!(decode_receipt(&trailing, None), Err(VerifyError::Decode(_))));
+    }
+
+    #[test]
+    fn test_parse_image_id() {
+        let mut expected = [0; 32];
+        expected[28..].copy_from_slice(&[0xab, 0xcd, 0x00, 0x01]);
+        let digits = format!("{}abcd0001", "0".repeat(56));
+        assert_eq!(parse_image_id(&digits).unwrap(), expected);
+        assert_eq!(parse_image_id(&format!("0x{}", digits.to_uppercase())).unwrap(), expected);
+        assert_eq!(parse_image_id(&"00".repeat(32)).unwrap(), [0; 32]);
+
+        let words = parse_image_id("[1, 2, 3, 4, 5, 6, 7, 0x8]");
+        assert!(matches!(words, Err(VerifyError::Decode(message)) if message.contains("invalid word \"0x8\"")));
+        let words = parse_image_id("[1, 2, 3, 4, 5, 6, 7, 4294967295]").unwrap();
+        assert_eq!(words[..8], [1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(words[28..], [0xff; 4]);
+
+        let error = |image_id: &str| match parse_image_id(image_id) {
+            Err(VerifyError::Decode(message)) => message,
+            result => panic!("expected an error for {:?}, got {:?}", image_id, result),
+        };
+        // Truncated ids are not padded.
+        assert!(error("abcd0001").contains("8 hex digits, expected 64"));
+        assert!(error("0xabc").contains("3 hex digits, expected 64"));
+        assert!(error(&"0".repeat(66)).contains("66 hex digits, expected 64"));
+        assert!(error("ab√d").contains("invalid hex digit '√' at position 2"));
+        assert!(error("[1, 2]").contains("2 words, expected 8"));
+        assert!(error("").contains("empty"));
+    }
+}
@@ -1,42 +1,77 @@
-use serde_json;
-use std::env;
+use std::path::{Path, PathBuf};
 
-use hyle_contract::HyleOutput;
+use clap::Parser;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <image_id> <receipt_path>", args[0]);
-        std::process::exit(1);
-    }
-    
-    // Image ID is the hexademical representation of the method ID, without leading prefix.
-    let image_id = &args[1];
+use risc0_verifier::program_outputs::{ProgramOutputs, ProgramOutputsFormat};
+use risc0_verifier::{compute_image_id, parse_image_id, ReceiptFormat, ReceiptKind, Risc0Proof, Risc0Verifier};
+use verifier_core::envelope::{Phase, Recorder};
+use verifier_core::checks::OutputArgs;
+use verifier_core::{exit_with, ProofOutputs, VerifyError};
 
-    // Parse the proof from file
-    let receipt_path = &args[2];
-    let receipt_content = std::fs::read_to_string(receipt_path).expect("Failed to read receipt file");
-    let receipt: risc0_zkvm::Receipt = serde_json::from_str(&receipt_content).expect("Failed to parse receipt file");
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Image id of the guest, as 64 hex digits with or without `0x` prefix, or as a `[u32; 8]` array.
+    image_id: String,
+    /// Receipt serialized in JSON, bincode or borsh, possibly wrapped in base64 or hex.
+    receipt_path: String,
+    /// Serialization of the receipt, detected from its content by default.
+    #[arg(long)]
+    format: Option<ReceiptFormat>,
+    /// Canonically encoded HyleOutput, for receipts whose journal only holds its commitment.
+    #[arg(long)]
+    output_path: Option<String>,
+    /// Receipt of an assumption of the guest, a claim of another guest it verified. Can be repeated.
+    #[arg(long = "assumption")]
+    assumptions: Vec<PathBuf>,
+    /// Kinds of receipt accepted, comma separated. All kinds are accepted by default.
+    #[arg(long, value_delimiter = ',')]
+    allowed_receipt_kinds: Vec<ReceiptKind>,
+    /// Decoding of the `program_outputs` following the fields of a risc0 serde journal: raw
+    /// bytes, or a JSON string. They are printed as `null` by default.
+    #[arg(long)]
+    program_outputs: Option<ProgramOutputsFormat>,
+    /// JSON file describing the type of the `program_outputs`, see `risc0_verifier::program_outputs::Schema`.
+    #[arg(long, conflicts_with = "program_outputs")]
+    program_outputs_schema: Option<PathBuf>,
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
 
-    let mut decoded_image_id: [u8; 32] = [0; 32];
-    for i in 0..image_id.len() / 2 {
-        decoded_image_id[i] = u8::from_str_radix(
-            &image_id
-                .get(i*2..i*2 + 2)
-                .expect("Invalid method ID string"),
-            16,
-        )
-        .expect("Invalid method ID string")
-    }
-    // Rotate to pad 0s in front.
-    decoded_image_id.rotate_right((64 - image_id.len()) / 2);
+/// `risc0-verifier image-id <guest.elf>`: prints the image id of a guest, in hex.
+#[derive(Parser, Debug)]
+#[command(name = "risc0-verifier image-id")]
+struct ImageIdCli {
+    elf_path: PathBuf,
+}
 
-    // perform verification
-    receipt
-        .verify(decoded_image_id)
-        .expect("Verification failed");
+fn run(args: &Cli, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    let (checks, image_id, proof, program_outputs) = recorder.time(Phase::Decode, || -> Result<_, VerifyError> {
+        let checks = args.output_args.checks()?;
+        let image_id = parse_image_id(&args.image_id)?;
+        // Parse the proof from file
+        let output_path = args.output_path.as_deref().map(Path::new);
+        let proof = Risc0Proof::read(Path::new(&args.receipt_path), args.format, output_path, &args.assumptions)?;
+        let program_outputs = ProgramOutputs::from_args(args.program_outputs, args.program_outputs_schema.as_deref())?;
+        Ok((checks, image_id, proof, program_outputs))
+    })?;
 
-    // Outputs to stdout for the caller to read.
-    let output: HyleOutput<()> = receipt.journal.decode().expect("Failed to decode receipt journal");
-    println!("{}", serde_json::to_string(&output).expect("Failed to serialize output"));
+    let verifier = Risc0Verifier { allowed_kinds: args.allowed_receipt_kinds.clone(), program_outputs };
+    let (outputs, info) = recorder.time(Phase::Verify, || verifier.verify_receipt(&image_id, &proof))?;
+    recorder.set_proof_info(serde_json::to_value(info).expect("receipt infos are serializable"));
+    checks.run_recorded(&outputs, recorder)?;
+    Ok(outputs)
+}
+
+fn main() {
+    // No image id is named so, it cannot be mistaken for the first argument of a verification.
+    if std::env::args().nth(1).as_deref() == Some("image-id") {
+        let cli = ImageIdCli::parse_from(std::env::args().skip(1));
+        exit_with(|| {
+            println!("{}", hex::encode(compute_image_id(&cli.elf_path)?));
+            Ok(())
+        })
+    }
+    let cli = Cli::parse();
+    let recorder = Recorder::new("risc0", Some(&cli.image_id), Path::new(&cli.receipt_path));
+    recorder.run(cli.output_args.bytes_format, cli.output_args.envelope, |recorder| run(&cli, recorder))
 }
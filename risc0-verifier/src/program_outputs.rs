@@ -0,0 +1,275 @@
+// Decoding of the `program_outputs` of journals committed with risc0 serde.
+//
+// Such journals are a sequence of 32-bit words: the protocol fields of the `HyleOutput`, then
+// the contract-specific `program_outputs`, whose type the verifier does not know. They are
+// ignored by default, or decoded as described by `ProgramOutputs`.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use verifier_core::checks::read_json;
+use verifier_core::VerifyError;
+
+/// How to decode the words of a journal following the protocol fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ProgramOutputs {
+    /// Printed as `null`.
+    #[default]
+    Ignored,
+    /// The words, as an array of little-endian bytes.
+    Raw,
+    /// A `String` holding JSON, as committed for `HyleOutput<String>`.
+    Json,
+    /// A value of the type described by the schema.
+    Schema(Schema),
+}
+
+/// Format of the `--program-outputs` flag, the schema is given in a file.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramOutputsFormat {
+    Raw,
+    Json,
+}
+
+/// Type of the program outputs, in the layout of risc0 serde. Written in JSON as the name of
+/// a primitive, such as `"u64"`, or an object naming a compound type, for instance
+/// `{"struct": [["amount", "u64"], ["memo", {"option": "string"}]]}`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Schema {
+    Primitive(Primitive),
+    Compound(Compound),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Primitive {
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    Char,
+    String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compound {
+    Option(Box<Schema>),
+    /// Length-prefixed sequence, such as a `Vec`.
+    Vec(Box<Schema>),
+    /// Fixed-size array, `[T; N]`.
+    Array(Box<Schema>, usize),
+    Tuple(Vec<Schema>),
+    /// Named fields, printed as an object.
+    Struct(Vec<(String, Schema)>),
+    /// Variants with their content, `null` for unit variants. Printed like serde_json does.
+    Enum(Vec<(String, Option<Schema>)>),
+}
+
+impl ProgramOutputs {
+    /// Reads the flags of the verifiers: `format`, or the schema in the JSON file at `schema`.
+    pub fn from_args(format: Option<ProgramOutputsFormat>, schema: Option<&Path>) -> Result<Self, VerifyError> {
+        match (format, schema) {
+            (None, None) => Ok(ProgramOutputs::Ignored),
+            (Some(ProgramOutputsFormat::Raw), None) => Ok(ProgramOutputs::Raw),
+            (Some(ProgramOutputsFormat::Json), None) => Ok(ProgramOutputs::Json),
+            (None, Some(schema)) => Ok(ProgramOutputs::Schema(read_json(schema)?)),
+            (Some(_), Some(_)) => {
+                Err(VerifyError::Decode("program outputs format and schema cannot be given together".to_string()))
+            }
+        }
+    }
+
+    /// Decodes `words`, all the words following the protocol fields.
+    pub fn decode(&self, words: &[u32]) -> Result<Value, VerifyError> {
+        let decode_error = |err: String| VerifyError::OutputDecode(format!("failed to decode program outputs: {}", err));
+        match self {
+            ProgramOutputs::Ignored => Ok(Value::Null),
+            ProgramOutputs::Raw => Ok(json!(words.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<u8>>())),
+            ProgramOutputs::Json => {
+                let mut reader = Words(words);
+                let json = reader.string().and_then(|json| reader.end().map(|()| json)).map_err(decode_error)?;
+                serde_json::from_str(&json).map_err(|err| decode_error(err.to_string()))
+            }
+            ProgramOutputs::Schema(schema) => {
+                let mut reader = Words(words);
+                let value = schema.decode(&mut reader).map_err(decode_error)?;
+                reader.end().map_err(decode_error)?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Reads words in the layout of risc0 serde.
+struct Words<'a>(&'a [u32]);
+
+impl<'a> Words<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u32], String> {
+        if self.0.len() < count {
+            return Err(format!("expected {} more words, {} left", count, self.0.len()));
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn word(&mut self) -> Result<u32, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Lengths are words, the bounds of the remaining words keeps them from exhausting memory.
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.word()? as usize;
+        if len > self.0.len() * 4 {
+            return Err(format!("length {} exceeds the {} words left", len, self.0.len()));
+        }
+        Ok(len)
+    }
+
+    /// 64-bit integers are two words, the low one first.
+    fn u64(&mut self) -> Result<u64, String> {
+        let low = self.word()? as u64;
+        let high = self.word()? as u64;
+        Ok(high << 32 | low)
+    }
+
+    /// Strings are their length in bytes, then their bytes padded to a word.
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        let bytes: Vec<u8> = self.take(len.div_ceil(4))?.iter().flat_map(|word| word.to_le_bytes()).take(len).collect();
+        String::from_utf8(bytes).map_err(|err| err.to_string())
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.0.len() {
+            0 => Ok(()),
+            left => Err(format!("{} trailing words", left)),
+        }
+    }
+}
+
+impl Schema {
+    fn decode(&self, words: &mut Words) -> Result<Value, String> {
+        match self {
+            Schema::Primitive(primitive) => primitive.decode(words),
+            Schema::Compound(compound) => compound.decode(words),
+        }
+    }
+}
+
+impl Primitive {
+    fn decode(&self, words: &mut Words) -> Result<Value, String> {
+        let value = match self {
+            Primitive::Unit => Value::Null,
+            Primitive::Bool => match words.word()? {
+                0 => json!(false),
+                1 => json!(true),
+                word => return Err(format!("invalid bool {}", word)),
+            },
+            Primitive::U8 => json!(u8::try_from(words.word()?).map_err(|err| err.to_string())?),
+            Primitive::U16 => json!(u16::try_from(words.word()?).map_err(|err| err.to_string())?),
+            Primitive::U32 => json!(words.word()?),
+            Primitive::U64 => json!(words.u64()?),
+            Primitive::I8 => json!(i8::try_from(words.word()? as i32).map_err(|err| err.to_string())?),
+            Primitive::I16 => json!(i16::try_from(words.word()? as i32).map_err(|err| err.to_string())?),
+            Primitive::I32 => json!(words.word()? as i32),
+            Primitive::I64 => json!(words.u64()? as i64),
+            Primitive::Char => {
+                let word = words.word()?;
+                json!(char::from_u32(word).ok_or_else(|| format!("invalid char {}", word))?)
+            }
+            Primitive::String => json!(words.string()?),
+        };
+        Ok(value)
+    }
+}
+
+impl Compound {
+    fn decode(&self, words: &mut Words) -> Result<Value, String> {
+        let value = match self {
+            Compound::Option(schema) => match words.word()? {
+                0 => Value::Null,
+                1 => schema.decode(words)?,
+                tag => return Err(format!("invalid option tag {}", tag)),
+            },
+            Compound::Vec(schema) => {
+                let len = words.len()?;
+                Value::Array((0..len).map(|_| schema.decode(words)).collect::<Result<_, _>>()?)
+            }
+            Compound::Array(schema, len) => {
+                Value::Array((0..*len).map(|_| schema.decode(words)).collect::<Result<_, _>>()?)
+            }
+            Compound::Tuple(schemas) => {
+                Value::Array(schemas.iter().map(|schema| schema.decode(words)).collect::<Result<_, _>>()?)
+            }
+            Compound::Struct(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, schema)| Ok((name.clone(), schema.decode(words)?)))
+                    .collect::<Result<Map<_, _>, String>>()?,
+            ),
+            Compound::Enum(variants) => {
+                let index = words.word()?;
+                let (name, content) =
+                    variants.get(index as usize).ok_or_else(|| format!("invalid variant index {}", index))?;
+                match content {
+                    None => json!(name),
+                    Some(schema) => json!({ name.clone(): schema.decode(words)? }),
+                }
+            }
+        };
+        Ok(value)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_schema() {
+        let schema: Schema = serde_json::from_value(json!({ "struct": [
+            ["amount", "u64"],
+            ["memo", { "option": "string" }],
+            ["flags", { "vec": "bool" }],
+            ["kind", { "enum": [["Mint", null], ["Transfer", { "tuple": ["u8", "i32"] }]] }],
+        ]}))
+        .unwrap();
+        let words = [
+            0x0000_0002, 0x0000_0001, // amount
+            1, 5, u32::from_le_bytes(*b"hell"), u32::from_le_bytes([b'o', 0, 0, 0]), // memo
+            2, 1, 0, // flags
+            1, 7, -3i32 as u32, // kind
+        ];
+        assert_eq!(
+            ProgramOutputs::Schema(schema.clone()).decode(&words).unwrap(),
+            json!({ "amount": (1u64 << 32) | 2, "memo": "hello", "flags": [true, false], "kind": { "Transfer": [7, -3] } })
+        );
+        assert!(matches!(ProgramOutputs::Schema(schema.clone()).decode(&words[..8]), Err(VerifyError::OutputDecode(_))));
+        let trailing = [&words[..], &[0]].concat();
+        assert!(matches!(ProgramOutputs::Schema(schema).decode(&trailing), Err(VerifyError::OutputDecode(_))));
+    }
+
+    #[test]
+    fn test_decode_json_and_raw() {
+        // The padding of the last word is not part of the string.
+        let words = [7, u32::from_le_bytes(*b"{\"a\""), u32::from_le_bytes(*b":1}\0")];
+        assert_eq!(ProgramOutputs::Json.decode(&words).unwrap(), json!({ "a": 1 }));
+        assert_eq!(ProgramOutputs::Raw.decode(&[1, 0x0100]).unwrap(), json!([1, 0, 0, 0, 0, 1, 0, 0]));
+        assert_eq!(ProgramOutputs::Ignored.decode(&[1]).unwrap(), Value::Null);
+        // Lengths beyond the journal are rejected before allocating.
+        assert!(ProgramOutputs::Json.decode(&[u32::MAX]).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+// Verification of SP1 proofs, see `verifier_core::Verifier`.
+
+use base64::prelude::*;
+use sp1_sdk::{ProverClient, SP1Proof, SP1VerifyingKey};
+
+use hyle_contract::{
+    encoding,
+    versions::{HyleOutputV1, OutputVersion},
+    HyleOutput,
+};
+use verifier_core::{decode_canonical, to_value, ProofOutputs, Verifier, VerifyError};
+
+pub struct Sp1Verifier {
+    client: ProverClient,
+}
+
+impl Sp1Verifier {
+    pub fn new() -> Self {
+        Sp1Verifier { client: ProverClient::new() }
+    }
+}
+
+impl Default for Sp1Verifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Verifier for Sp1Verifier {
+    type ProgramId = SP1VerifyingKey;
+    type Proof = SP1Proof;
+
+    fn verify_outputs(&self, vk: &SP1VerifyingKey, proof: &SP1Proof) -> Result<ProofOutputs, VerifyError> {
+        // TODO: check that vk is correct ?
+        self.client.verify(proof, vk).map_err(|err| VerifyError::InvalidProof(format!("{:?}", err)))?;
+
+        // Guests committing the canonical encoding are decoded with it, others with SP1 serde.
+        let public_values = proof.public_values.as_slice();
+        if encoding::is_canonical(public_values) || encoding::is_canonical_batch(public_values) {
+            return decode_canonical(public_values);
+        }
+        // The version is the first u32 of the public values, it selects the layout to decode.
+        let version = public_values
+            .get(0..4)
+            .ok_or_else(|| VerifyError::OutputDecode("empty public values".to_string()))?;
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        let version = OutputVersion::try_from(version).map_err(|err| VerifyError::OutputDecode(err.to_string()))?;
+        // SP1 panics on public values which do not decode.
+        let mut public_values = proof.public_values.clone();
+        let output: HyleOutput<()> = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || match version {
+            OutputVersion::V0 | OutputVersion::V1 => public_values.read::<HyleOutputV1<()>>().into(),
+            OutputVersion::V2 => public_values.read::<HyleOutput<()>>(),
+        }))
+        .map_err(|_| VerifyError::OutputDecode("failed to decode public values".to_string()))?;
+        Ok(ProofOutputs::Single(to_value(output)?))
+    }
+}
+
+/// Parses a base64 encoded JSON verification key.
+pub fn parse_verification_key(b64_encoded_verification_key: &str) -> Result<SP1VerifyingKey, VerifyError> {
+    let vk_json = BASE64_STANDARD
+        .decode(b64_encoded_verification_key)
+        .map_err(|err| VerifyError::Decode(format!("vk decoding failed: {}", err)))?;
+    let vk_json = String::from_utf8(vk_json).map_err(|_| VerifyError::Decode("vk is not a JSON string".to_string()))?;
+    Ok(SP1VerifyingKey {
+        vk: serde_json::from_str(&vk_json).map_err(|err| VerifyError::Decode(format!("vk parsing failed: {}", err)))?,
+    })
+}
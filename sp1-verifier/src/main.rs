@@ -1,31 +1,38 @@
-use std::env;
-
-use base64::prelude::*;
-
-use sp1_sdk::{ProverClient, SP1Proof, SP1VerifyingKey};
+use std::path::Path;
+
+use clap::Parser;
+
+use sp1_sdk::SP1Proof;
+use sp1_verifier::{parse_verification_key, Sp1Verifier};
+use verifier_core::envelope::{Phase, Recorder};
+use verifier_core::checks::OutputArgs;
+use verifier_core::{ProofOutputs, Verifier, VerifyError};
+
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Base64 encoded JSON verification key.
+    b64_encoded_verification_key: String,
+    receipt_path: String,
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
 
-use hyle_contract::HyleOutput;
+fn run(args: &Cli, recorder: &mut Recorder) -> Result<ProofOutputs, VerifyError> {
+    let (checks, vk, proof) = recorder.time(Phase::Decode, || -> Result<_, VerifyError> {
+        let checks = args.output_args.checks()?;
+        let vk = parse_verification_key(&args.b64_encoded_verification_key)?;
+        let proof = SP1Proof::load(&args.receipt_path)
+            .map_err(|err| VerifyError::Decode(format!("loading proof failed: {}", err)))?;
+        Ok((checks, vk, proof))
+    })?;
+
+    let outputs = recorder.time(Phase::Verify, || Sp1Verifier::new().verify_outputs(&vk, &proof))?;
+    checks.run_recorded(&outputs, recorder)?;
+    Ok(outputs)
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <b64_encoded_verification_key> <receipt_path>", args[0]);
-        std::process::exit(1);
-    }
-
-    let vk_json = String::from_utf8(BASE64_STANDARD.decode(&args[1]).expect("vk decoding failed")).expect("Fail to cast vk to json string");
-
-    let vk: SP1VerifyingKey = SP1VerifyingKey{
-        vk: serde_json::from_str(&vk_json).unwrap()
-    };
-    let mut proof = SP1Proof::load(&args[2]).expect("loading proof failed");
-
-    // TODO: check that vk is correct ?
-    let prover_client = ProverClient::new();
-    prover_client.verify(&proof, &vk).expect("verification failed");
-
-    // Outputs to stdout for the caller to read.
-    let output: HyleOutput<()> = proof.public_values.read::<HyleOutput<()>>();
-    println!("{}", serde_json::to_string(&output).expect("Failed to serialize output"));
-
+    let cli = Cli::parse();
+    let recorder = Recorder::new("sp1", Some(&cli.b64_encoded_verification_key), Path::new(&cli.receipt_path));
+    recorder.run(cli.output_args.bytes_format, cli.output_args.envelope, |recorder| run(&cli, recorder))
 }
@@ -0,0 +1,188 @@
+// On-disk cache of verified outputs, so that a proof submitted again is not verified again.
+//
+// Entries are keyed by the digest of the backend, program id, proof and verifier build, see
+// `Cache::key` and `build_id`, and hold the outputs of the proof as printed in hex, along with its
+// `Envelope::proof_info`. Only successful verifications are cached: the checks requested along
+// with a proof still run on each request.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use hyle_contract::readable::BytesFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{ProofOutputs, VerifyError};
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// What is cached of a verification.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    /// Outputs of the proof, printed in hex.
+    pub output: Value,
+    #[serde(default)]
+    pub proof_info: Option<Value>,
+}
+
+/// Result of `Cache::prune`.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    pub removed: usize,
+    pub kept: usize,
+}
+
+/// SHA-256 digest of the file at `path`, in hex, if it can be read. Also the `proof_digest` of
+/// the envelope.
+pub fn file_digest(path: &Path) -> Option<String> {
+    std::fs::read(path).ok().map(|bytes| hex::encode(Sha256::digest(bytes)))
+}
+
+/// Identifies the running verifier for `Cache::key`: the digest of its executable. Unlike the
+/// crate version, it changes with the versions of the backends linked in. `None` if the
+/// executable cannot be read, results should not be cached then.
+pub fn build_id() -> Option<&'static str> {
+    static BUILD_ID: OnceLock<Option<String>> = OnceLock::new();
+    BUILD_ID.get_or_init(|| std::env::current_exe().ok().and_then(|exe| file_digest(&exe))).as_deref()
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Cache { dir: dir.into() }
+    }
+
+    /// Key of the verification of a proof: `files` are the proof, then the other files its
+    /// outputs are read from, such as an output given out of band. `version` identifies the
+    /// verifier, see `build_id`. `None` if a file cannot be read, the verification then reports
+    /// the error.
+    pub fn key(backend: &str, program_id: Option<&str>, files: &[&Path], version: &str) -> Option<String> {
+        let mut hasher = Sha256::new();
+        // Fields are length-prefixed, so that they cannot be shifted into one another.
+        let mut field = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(backend.as_bytes());
+        field(program_id.unwrap_or_default().as_bytes());
+        field(version.as_bytes());
+        for file in files {
+            field(&std::fs::read(file).ok()?);
+        }
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Outputs and proof info cached for `key`. Entries which cannot be read are ignored.
+    pub fn get(&self, key: &str) -> Option<(ProofOutputs, Option<Value>)> {
+        let json = std::fs::read(self.path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&json).ok()?;
+        Some((ProofOutputs::from_json(entry.output).ok()?, entry.proof_info))
+    }
+
+    /// Caches `outputs` and `proof_info` for `key`. The entry is written to a temporary file
+    /// first, so that concurrent verifiers never read a partial entry.
+    pub fn put(&self, key: &str, outputs: &ProofOutputs, proof_info: Option<&Value>) -> Result<(), VerifyError> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let io_error = |err: std::io::Error| VerifyError::Internal(format!("failed to write cache entry {}: {}", key, err));
+        std::fs::create_dir_all(&self.dir).map_err(io_error)?;
+        let tmp = self.dir.join(format!("{}.{}.{}.tmp", key, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let entry = CacheEntry { output: outputs.clone().to_json(BytesFormat::Hex), proof_info: proof_info.cloned() };
+        let json = serde_json::to_vec(&entry).expect("cache entries are serializable");
+        let written = std::fs::File::create(&tmp).and_then(|mut file| file.write_all(&json));
+        if let Err(err) = written.and_then(|()| std::fs::rename(&tmp, self.path(key))) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(io_error(err));
+        }
+        Ok(())
+    }
+
+    /// Removes the entries written more than `older_than` ago, or all entries if `None`.
+    pub fn prune(&self, older_than: Option<Duration>) -> Result<PruneStats, VerifyError> {
+        let io_error = |err: std::io::Error| VerifyError::Internal(format!("failed to prune {}: {}", self.dir.display(), err));
+        let mut stats = PruneStats::default();
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(stats),
+            Err(err) => return Err(io_error(err)),
+        };
+        let now = SystemTime::now();
+        for entry in entries {
+            let path = entry.map_err(io_error)?.path();
+            if !matches!(path.extension().and_then(|ext| ext.to_str()), Some("json" | "tmp")) {
+                continue;
+            }
+            let age = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default())
+                .map_err(io_error)?;
+            let expired = match older_than {
+                Some(older_than) => age >= older_than,
+                None => true,
+            };
+            if expired {
+                std::fs::remove_file(&path).map_err(io_error)?;
+                stats.removed += 1;
+            } else {
+                stats.kept += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::to_value;
+    use hyle_contract::HyleOutput;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("hyle-verifier-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let proof = dir.join("proof.bin");
+        std::fs::write(&proof, [1, 2, 3]).unwrap();
+
+        let key = Cache::key("risc0", Some("ab"), &[&proof], "0.1.0").unwrap();
+        assert_ne!(Cache::key("risc0", Some("ab"), &[&proof], "0.2.0"), Some(key.clone()));
+        assert_ne!(Cache::key("risc0", Some("a"), &[&proof], "b0.1.0"), Some(key.clone()));
+        assert_eq!(Cache::key("risc0", Some("ab"), &[&proof, &dir.join("missing")], "0.1.0"), None);
+        assert_eq!(build_id(), build_id());
+        assert_eq!(build_id().map(str::len), Some(64));
+
+        let cache = Cache::new(dir.join("cache"));
+        assert_eq!(cache.get(&key), None);
+        let output = HyleOutput {
+            version: 2,
+            initial_state: vec![1],
+            next_state: vec![2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: vec![7u8],
+        };
+        let outputs = ProofOutputs::Single(to_value(output).unwrap());
+        let proof_info = serde_json::json!({ "receipt_kind": "succinct" });
+        cache.put(&key, &outputs, Some(&proof_info)).unwrap();
+        assert_eq!(cache.get(&key), Some((outputs, Some(proof_info))));
+
+        assert_eq!(cache.prune(Some(Duration::from_secs(3600))).unwrap(), PruneStats { removed: 0, kept: 1 });
+        assert_eq!(cache.prune(None).unwrap(), PruneStats { removed: 1, kept: 0 });
+        assert_eq!(cache.get(&key), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
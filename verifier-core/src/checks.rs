@@ -0,0 +1,425 @@
+// Checks requested by the caller, run on the outputs once the proof is verified.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use hyle_contract::readable::{BytesFormat, ReadableBytes};
+use hyle_contract::state::{KvState, StateDiff};
+use hyle_contract::validation::{ExpectedContext, Mismatch};
+use hyle_contract::HyleOutput;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::envelope::{Phase, Recorder};
+use crate::{ProofOutputs, VerifyError};
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputChecks {
+    pub expected_context: Option<ExpectedContext>,
+    /// Contract state before the transaction and its diff, see `hyle_contract::state`.
+    pub state_transition: Option<(KvState, StateDiff)>,
+    /// Fields given with the `--expect-*` flags, see `ExpectArgs`.
+    pub expected_fields: Option<ExpectedContext>,
+    /// Output given with `--expect-output`.
+    pub expected_output: Option<Value>,
+}
+
+/// Values the caller expects in the outputs, shared by all verifiers. Mismatches fail the
+/// verification with the list of `FieldDiff` found.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ExpectArgs {
+    /// Expected HyleOutput version.
+    #[arg(long)]
+    pub expect_version: Option<u32>,
+    /// Hex initial state, of the first output of a batch.
+    #[arg(long)]
+    pub expect_initial_state: Option<String>,
+    /// Expected origin of the transaction.
+    #[arg(long)]
+    pub expect_origin: Option<String>,
+    /// Expected caller of the contract.
+    #[arg(long)]
+    pub expect_caller: Option<String>,
+    /// Lowest block number accepted.
+    #[arg(long)]
+    pub expect_min_block_number: Option<u64>,
+    /// Highest block number accepted.
+    #[arg(long)]
+    pub expect_max_block_number: Option<u64>,
+    /// Lowest block time accepted.
+    #[arg(long)]
+    pub expect_min_block_time: Option<u64>,
+    /// Highest block time accepted.
+    #[arg(long)]
+    pub expect_max_block_time: Option<u64>,
+    /// Hex transaction hash. Rejected for batches, whose outputs each have their own.
+    #[arg(long)]
+    pub expect_tx_hash: Option<String>,
+    /// JSON file holding the output as printed, in any bytes format, or the array of outputs of a
+    /// batch. Fields left out are not compared.
+    #[arg(long)]
+    pub expect_output: Option<PathBuf>,
+}
+
+/// Checks run on the outputs and format they are printed in, shared by all verifiers.
+#[derive(clap::Args, Deserialize, Debug, Clone, Default)]
+pub struct OutputArgs {
+    /// JSON file describing the expected execution context, checked against the output.
+    #[arg(long)]
+    pub expected_context: Option<PathBuf>,
+    /// JSON file holding the contract state before the transaction, see `hyle_contract::state`.
+    #[arg(long, requires = "state_diff")]
+    pub prior_state: Option<PathBuf>,
+    /// JSON file holding the state diff of the transaction, checked against the prior state.
+    #[arg(long, requires = "prior_state")]
+    pub state_diff: Option<PathBuf>,
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub expect: ExpectArgs,
+    /// Format of the bytes fields in the printed output: array, hex or base64.
+    #[arg(long, default_value = "array")]
+    #[serde(default)]
+    pub bytes_format: BytesFormat,
+    /// Print a JSON envelope with the status, error and timings of the verification, see `verifier_core::envelope`.
+    #[arg(long)]
+    #[serde(skip)]
+    pub envelope: bool,
+}
+
+/// A field of an output which differs from the expected one. Bytes are shown in hex.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// Index of the output in a batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<usize>,
+    pub field: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(output) = self.output {
+            write!(f, "output {}: ", output)?;
+        }
+        write!(f, "{}: expected {}, got {}", self.field, self.expected, self.actual)
+    }
+}
+
+fn parse_hex(flag: &str, value: &str) -> Result<Vec<u8>, VerifyError> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value))
+        .map_err(|err| VerifyError::Decode(format!("invalid {} {:?}: {}", flag, value, err)))
+}
+
+impl ExpectArgs {
+    fn expected_fields(&self) -> Result<Option<ExpectedContext>, VerifyError> {
+        let expected = ExpectedContext {
+            version: self.expect_version,
+            initial_state: self
+                .expect_initial_state
+                .as_deref()
+                .map(|value| parse_hex("--expect-initial-state", value))
+                .transpose()?,
+            origin: self.expect_origin.clone(),
+            caller: self.expect_caller.clone(),
+            min_block_number: self.expect_min_block_number,
+            max_block_number: self.expect_max_block_number,
+            min_block_time: self.expect_min_block_time,
+            max_block_time: self.expect_max_block_time,
+            tx_hash: self.expect_tx_hash.as_deref().map(|value| parse_hex("--expect-tx-hash", value)).transpose()?,
+        };
+        Ok((expected != ExpectedContext::default()).then_some(expected))
+    }
+}
+
+impl OutputArgs {
+    /// Reads the checks requested by the flags.
+    pub fn checks(&self) -> Result<OutputChecks, VerifyError> {
+        OutputChecks::from_files(self.expected_context.as_deref(), self.prior_state.as_deref(), self.state_diff.as_deref())?
+            .with_expectations(&self.expect)
+    }
+}
+
+fn hex_value(data: Vec<u8>) -> Value {
+    json!(ReadableBytes { data, format: BytesFormat::Hex })
+}
+
+impl FieldDiff {
+    fn from_mismatch(output: Option<usize>, mismatch: Mismatch) -> Self {
+        let (field, expected, actual) = match mismatch {
+            Mismatch::Version { expected, actual } => ("version", json!(expected), json!(actual)),
+            Mismatch::InitialState { expected, actual } => ("initial_state", hex_value(expected), hex_value(actual)),
+            Mismatch::Origin { expected, actual } => ("origin", json!(expected), json!(actual)),
+            Mismatch::Caller { expected, actual } => ("caller", json!(expected), json!(actual)),
+            Mismatch::BlockNumber { min, max, actual } => ("block_number", json!({ "min": min, "max": max }), json!(actual)),
+            Mismatch::BlockTime { min, max, actual } => ("block_time", json!({ "min": min, "max": max }), json!(actual)),
+            Mismatch::TxHash { expected, actual } => ("tx_hash", hex_value(expected), hex_value(actual)),
+        };
+        FieldDiff { output, field: field.to_string(), expected, actual }
+    }
+}
+
+/// Compares `output` with the fields of `expected`, a printed output.
+fn diff_output(index: Option<usize>, output: &HyleOutput<Value>, expected: &Value) -> Result<Vec<FieldDiff>, VerifyError> {
+    let printed = |output: HyleOutput<Value>| {
+        serde_json::to_value(output.readable(BytesFormat::Hex)).expect("outputs are serializable")
+    };
+    let (Value::Object(expected), Value::Object(actual)) = (expected, printed(output.clone())) else {
+        return Err(VerifyError::Decode("expected output must be a JSON object".to_string()));
+    };
+    // Fields left out are taken from the actual output, then the whole output is parsed so that
+    // bytes are compared whatever their format.
+    let mut merged = actual.clone();
+    for (field, value) in expected {
+        if !merged.contains_key(field) {
+            return Err(VerifyError::Decode(format!("unknown field {:?} in expected output", field)));
+        }
+        merged.insert(field.clone(), value.clone());
+    }
+    let expected_output = match ProofOutputs::from_json(Value::Object(merged)) {
+        Ok(ProofOutputs::Single(output)) => printed(output),
+        Ok(ProofOutputs::Batch(_)) => unreachable!("objects parse as single outputs"),
+        Err(err) => return Err(VerifyError::Decode(format!("invalid expected output: {}", err.message()))),
+    };
+    Ok(expected
+        .keys()
+        .filter(|field| expected_output[field.as_str()] != actual[field.as_str()])
+        .map(|field| FieldDiff {
+            output: index,
+            field: field.clone(),
+            expected: expected_output[field.as_str()].clone(),
+            actual: actual[field.as_str()].clone(),
+        })
+        .collect())
+}
+
+/// Compares `outputs` with `expected`, as given with `--expect-output`.
+fn diff_outputs(outputs: &ProofOutputs, expected: &Value) -> Result<Vec<FieldDiff>, VerifyError> {
+    match (outputs, expected) {
+        (ProofOutputs::Single(output), _) if !expected.is_array() => diff_output(None, output, expected),
+        (ProofOutputs::Batch(batch), Value::Array(expected)) => {
+            if batch.outputs.len() != expected.len() {
+                return Ok(vec![FieldDiff {
+                    output: None,
+                    field: "outputs".to_string(),
+                    expected: json!(expected.len()),
+                    actual: json!(batch.outputs.len()),
+                }]);
+            }
+            let diffs = batch.outputs.iter().zip(expected).enumerate().map(|(i, (output, expected))| {
+                diff_output(Some(i), output, expected)
+            });
+            Ok(diffs.collect::<Result<Vec<_>, _>>()?.concat())
+        }
+        (ProofOutputs::Single(_), _) => {
+            Err(VerifyError::PolicyViolation("expected a batch of outputs, the proof holds a single output".to_string()))
+        }
+        (ProofOutputs::Batch(_), _) => {
+            Err(VerifyError::PolicyViolation("expected a single output, the proof holds a batch".to_string()))
+        }
+    }
+}
+
+/// Reads a JSON file given on the command line.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, VerifyError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| VerifyError::Decode(format!("failed to read {}: {}", path.display(), err)))?;
+    serde_json::from_str(&content)
+        .map_err(|err| VerifyError::Decode(format!("failed to parse {}: {}", path.display(), err)))
+}
+
+impl OutputChecks {
+    /// Reads the checks from the JSON files given on the command line.
+    pub fn from_files(
+        expected_context: Option<&Path>,
+        prior_state: Option<&Path>,
+        state_diff: Option<&Path>,
+    ) -> Result<Self, VerifyError> {
+        let state_transition = match (prior_state, state_diff) {
+            (Some(prior_state), Some(state_diff)) => Some((read_json(prior_state)?, read_json(state_diff)?)),
+            (None, None) => None,
+            _ => return Err(VerifyError::Decode("prior state and state diff must be given together".to_string())),
+        };
+        Ok(OutputChecks {
+            expected_context: expected_context.map(read_json).transpose()?,
+            state_transition,
+            ..Default::default()
+        })
+    }
+
+    /// Adds the checks of the `--expect-*` flags.
+    pub fn with_expectations(self, args: &ExpectArgs) -> Result<Self, VerifyError> {
+        Ok(OutputChecks {
+            expected_fields: args.expected_fields()?,
+            expected_output: args.expect_output.as_deref().map(read_json).transpose()?,
+            ..self
+        })
+    }
+
+    /// Fails if `outputs` do not pass the checks. Returns the keys touched by the state diff, if checked.
+    pub fn run(&self, outputs: &ProofOutputs) -> Result<Option<Vec<Vec<u8>>>, VerifyError> {
+        // The expected context and the `--expect-*` flags are checked alike, and their
+        // mismatches reported together.
+        let mut diffs = Vec::new();
+        for expected in [&self.expected_context, &self.expected_fields].into_iter().flatten() {
+            match outputs {
+                ProofOutputs::Single(output) => diffs.extend(
+                    output.validate(expected).err().unwrap_or_default().into_iter().map(|m| FieldDiff::from_mismatch(None, m)),
+                ),
+                ProofOutputs::Batch(_) if expected.tx_hash.is_some() => {
+                    return Err(VerifyError::PolicyViolation(
+                        "a tx_hash cannot be expected from a batch, expect the output of each transaction instead"
+                            .to_string(),
+                    ))
+                }
+                ProofOutputs::Batch(batch) => diffs.extend(
+                    batch
+                        .validate(expected)
+                        .err()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(i, m)| FieldDiff::from_mismatch(Some(i), m)),
+                ),
+            }
+        }
+        if let Some(expected) = &self.expected_output {
+            diffs.extend(diff_outputs(outputs, expected)?);
+        }
+        if !diffs.is_empty() {
+            return Err(VerifyError::OutputMismatch(diffs));
+        }
+
+        let Some((prior_state, state_diff)) = &self.state_transition else {
+            return Ok(None);
+        };
+        let transition = match outputs {
+            ProofOutputs::Single(output) => output.check_state_diff(prior_state, state_diff),
+            ProofOutputs::Batch(batch) => batch.check_state_diff(prior_state, state_diff),
+        };
+        transition.map_err(|err| VerifyError::PolicyViolation(format!("state transition mismatch: {}", err)))?;
+        Ok(Some(state_diff.touched_keys().into_iter().cloned().collect()))
+    }
+
+    /// Runs the checks as the `Checks` phase of `recorder`, reporting the keys touched by the
+    /// state diff to it.
+    pub fn run_recorded(&self, outputs: &ProofOutputs, recorder: &mut Recorder) -> Result<(), VerifyError> {
+        if let Some(touched_keys) = recorder.time(Phase::Checks, || self.run(outputs))? {
+            recorder.set_touched_keys(&touched_keys);
+        }
+        Ok(())
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::to_value;
+    use hyle_contract::batch::HyleBatchOutput;
+
+    fn outputs() -> ProofOutputs {
+        let output = HyleOutput {
+            version: 2,
+            initial_state: vec![1],
+            next_state: vec![2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![0xab],
+            events: vec![],
+            program_outputs: vec![7u8],
+        };
+        ProofOutputs::Single(to_value(output).unwrap())
+    }
+
+    #[test]
+    fn test_expectations() {
+        let args = ExpectArgs {
+            expect_version: Some(2),
+            expect_initial_state: Some("0x01".to_string()),
+            expect_origin: Some("alice".to_string()),
+            expect_tx_hash: Some("ab".to_string()),
+            expect_min_block_number: Some(42),
+            ..Default::default()
+        };
+        let checks = OutputChecks::default().with_expectations(&args).unwrap();
+        assert_eq!(checks.run(&outputs()), Ok(None));
+
+        let args = ExpectArgs {
+            expect_origin: Some("carol".to_string()),
+            expect_tx_hash: Some("0xcd".to_string()),
+            expect_min_block_number: Some(43),
+            ..args
+        };
+        let checks = OutputChecks {
+            expected_output: Some(json!({ "next_state": [2], "block_number": 41, "program_outputs": [7] })),
+            ..OutputChecks::default().with_expectations(&args).unwrap()
+        };
+        let Err(VerifyError::OutputMismatch(diffs)) = checks.run(&outputs()) else { panic!("expected a mismatch") };
+        let diff = |field: &str, expected: Value, actual: Value| FieldDiff { output: None, field: field.to_string(), expected, actual };
+        assert_eq!(
+            diffs,
+            vec![
+                diff("origin", json!("carol"), json!("alice")),
+                diff("block_number", json!({ "min": 43, "max": null }), json!(42)),
+                diff("tx_hash", json!("0xcd"), json!("0xab")),
+                diff("block_number", json!(41), json!(42)),
+            ]
+        );
+
+        // The expected context is reported like the flags.
+        let checks = OutputChecks {
+            expected_context: Some(ExpectedContext { caller: Some("carol".to_string()), ..Default::default() }),
+            ..Default::default()
+        };
+        assert_eq!(
+            checks.run(&outputs()),
+            Err(VerifyError::OutputMismatch(vec![diff("caller", json!("carol"), json!("bob"))]))
+        );
+
+        let args = ExpectArgs { expect_tx_hash: Some("xyz".to_string()), ..Default::default() };
+        assert!(matches!(OutputChecks::default().with_expectations(&args), Err(VerifyError::Decode(_))));
+        let checks = OutputChecks { expected_output: Some(json!({ "state": [1] })), ..Default::default() };
+        assert!(matches!(checks.run(&outputs()), Err(VerifyError::Decode(_))));
+    }
+
+    #[test]
+    fn test_batch_expectations() {
+        let ProofOutputs::Single(first) = outputs() else { unreachable!() };
+        let mut second = first.clone();
+        second.initial_state = vec![2];
+        second.next_state = vec![3];
+        second.tx_hash = vec![0xcd];
+        let batch = ProofOutputs::Batch(HyleBatchOutput { outputs: vec![first, second] });
+
+        // Context fields shared by the transactions are expected from each output.
+        let args = ExpectArgs {
+            expect_initial_state: Some("01".to_string()),
+            expect_origin: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(OutputChecks::default().with_expectations(&args).unwrap().run(&batch), Ok(None));
+
+        let args = ExpectArgs { expect_tx_hash: Some("ab".to_string()), ..args };
+        let checks = OutputChecks::default().with_expectations(&args).unwrap();
+        assert!(matches!(checks.run(&batch), Err(VerifyError::PolicyViolation(_))));
+        let checks = OutputChecks {
+            expected_context: Some(ExpectedContext { tx_hash: Some(vec![0xab]), ..Default::default() }),
+            ..Default::default()
+        };
+        assert!(matches!(checks.run(&batch), Err(VerifyError::PolicyViolation(_))));
+
+        // The tx_hash of each output can be expected with --expect-output.
+        let checks = OutputChecks {
+            expected_output: Some(json!([{ "tx_hash": "0xab" }, { "tx_hash": "0xab" }])),
+            ..Default::default()
+        };
+        let Err(VerifyError::OutputMismatch(diffs)) = checks.run(&batch) else { panic!("expected a mismatch") };
+        let diff = FieldDiff { output: Some(1), field: "tx_hash".to_string(), expected: json!("0xab"), actual: json!("0xcd") };
+        assert_eq!(diffs, vec![diff]);
+    }
+}
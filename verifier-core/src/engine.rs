@@ -0,0 +1,240 @@
+// Runs many verifications in parallel. Each job is isolated: a job which panics or exceeds its
+// timeout fails alone, and the others still report their result.
+//
+// A running thread cannot be stopped, so a job which times out is detached and keeps running in
+// the background. It still counts against `Engine::concurrency` until it ends: a job waits up to
+// the timeout for a free slot, and fails if the timed out jobs hold them all. A job which never
+// returns thus cannot block the others. Jobs which spawn processes should kill them once the
+// timeout is reached.
+
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::panic_message;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    Panicked(String),
+    /// The job is left running in the background, its result is dropped. It holds its slot of
+    /// `Engine::concurrency` until it ends.
+    TimedOut(Duration),
+    /// Jobs which timed out held every slot for this long, the job was not run.
+    NoSlot(Duration),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Panicked(msg) => write!(f, "job panicked: {}", msg),
+            JobError::TimedOut(timeout) => write!(f, "job timed out after {:?}", timeout),
+            JobError::NoSlot(timeout) => write!(f, "no slot freed by the timed out jobs within {:?}", timeout),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+#[derive(Debug, Clone)]
+pub struct Engine {
+    /// Number of jobs run at once, including the timed out jobs still running.
+    pub concurrency: usize,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Engine {
+    /// One job per available CPU, without timeout.
+    fn default() -> Self {
+        let concurrency = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Engine { concurrency, timeout: None }
+    }
+}
+
+/// Number of job threads running, detached ones included.
+#[derive(Default)]
+struct Slots {
+    running: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Slot taken by a job thread, freed when dropped.
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    /// Takes one of the `limit` slots, waiting up to `timeout` for one to be freed.
+    fn acquire(slots: &Arc<Slots>, limit: usize, timeout: Duration) -> Option<Slot> {
+        let deadline = Instant::now() + timeout;
+        let mut running = slots.running.lock().unwrap_or_else(|err| err.into_inner());
+        while *running >= limit {
+            let remaining = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())?;
+            running = slots.freed.wait_timeout(running, remaining).unwrap_or_else(|err| err.into_inner()).0;
+        }
+        *running += 1;
+        Some(Slot(slots.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap_or_else(|err| err.into_inner()) -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+impl Engine {
+    /// Runs `job` on each input, returning the results in the order of `inputs`.
+    pub fn run<I, R, F>(&self, inputs: Vec<I>, job: F) -> Vec<Result<R, JobError>>
+    where
+        I: Send + 'static,
+        R: Send + 'static,
+        F: Fn(I) -> R + Send + Sync + 'static,
+    {
+        let count = inputs.len();
+        let job = Arc::new(job);
+        let queue = Mutex::new(inputs.into_iter().enumerate());
+        let slots = Arc::new(Slots::default());
+        let (results_tx, results_rx) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..self.concurrency.clamp(1, count.max(1)) {
+                let results_tx = results_tx.clone();
+                let (job, queue, slots) = (&job, &queue, &slots);
+                scope.spawn(move || loop {
+                    // Release the queue before running the job.
+                    let next = queue.lock().unwrap_or_else(|err| err.into_inner()).next();
+                    let Some((index, input)) = next else { break };
+                    let _ = results_tx.send((index, self.run_job(job, input, slots)));
+                });
+            }
+        });
+        drop(results_tx);
+
+        let mut results: Vec<Option<Result<R, JobError>>> = (0..count).map(|_| None).collect();
+        for (index, result) in results_rx {
+            results[index] = Some(result);
+        }
+        results.into_iter().map(|result| result.expect("every job reports its result")).collect()
+    }
+
+    /// Runs a single job. With a timeout, the job runs in its own thread holding one of `slots`.
+    fn run_job<I, R, F>(&self, job: &Arc<F>, input: I, slots: &Arc<Slots>) -> Result<R, JobError>
+    where
+        I: Send + 'static,
+        R: Send + 'static,
+        F: Fn(I) -> R + Send + Sync + 'static,
+    {
+        let catch = |job: &F, input: I| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(input)))
+                .map_err(|payload| JobError::Panicked(panic_message(payload.as_ref())))
+        };
+        let Some(timeout) = self.timeout else {
+            return catch(job, input);
+        };
+        let slot = Slots::acquire(slots, self.concurrency.max(1), timeout).ok_or(JobError::NoSlot(timeout))?;
+        // The job runs in its own thread, which is left behind if it times out.
+        let (tx, rx) = mpsc::channel();
+        let job = job.clone();
+        std::thread::spawn(move || {
+            let result = catch(&job, input);
+            // Free the slot before reporting, so that the next job of this worker finds it free.
+            drop(slot);
+            let _ = tx.send(result);
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(JobError::TimedOut(timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(JobError::Panicked("job thread exited".to_string())),
+        }
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run_in_order() {
+        let engine = Engine { concurrency: 3, timeout: None };
+        let results = engine.run((0..20).collect(), |i: u64| {
+            std::thread::sleep(Duration::from_millis(20 - i));
+            i * 2
+        });
+        assert_eq!(results, (0..20).map(|i| Ok(i * 2)).collect::<Vec<_>>());
+        assert!(engine.run(Vec::<u64>::new(), |i| i).is_empty());
+    }
+
+    #[test]
+    fn test_isolate_failures() {
+        for timeout in [None, Some(Duration::from_millis(200))] {
+            let engine = Engine { concurrency: 2, timeout };
+            let results = engine.run(vec![0, 1, 2], |i: u64| {
+                if i == 1 {
+                    panic!("bad proof {}", i);
+                }
+                i
+            });
+            assert_eq!(results, vec![Ok(0), Err(JobError::Panicked("bad proof 1".to_string())), Ok(2)]);
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let timeout = Duration::from_millis(50);
+        // Job 2 runs next to job 1, which holds its slot until it ends.
+        let engine = Engine { concurrency: 2, timeout: Some(timeout) };
+        let results = engine.run(vec![0, 1, 2], |i: u64| {
+            if i == 1 {
+                std::thread::sleep(Duration::from_secs(2));
+            }
+            i
+        });
+        assert_eq!(results, vec![Ok(0), Err(JobError::TimedOut(timeout)), Ok(2)]);
+    }
+
+    #[test]
+    fn test_timed_out_jobs_hold_their_slot() {
+        // Jobs 0, 2 and 4 time out, and end while the following jobs wait for their slot.
+        let engine = Engine { concurrency: 2, timeout: Some(Duration::from_millis(100)) };
+        let running = Arc::new(Mutex::new((0, 0)));
+        let results = engine.run((0..6).collect(), {
+            let running = running.clone();
+            move |i: u64| {
+                {
+                    let mut running = running.lock().unwrap();
+                    running.0 += 1;
+                    running.1 = running.1.max(running.0);
+                }
+                std::thread::sleep(Duration::from_millis(if matches!(i, 0 | 2 | 4) { 150 } else { 1 }));
+                running.lock().unwrap().0 -= 1;
+                i
+            }
+        });
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 3);
+        // The jobs which timed out were still running when the following ones started.
+        assert_eq!(running.lock().unwrap().1, 2);
+    }
+
+    #[test]
+    fn test_job_never_returns() {
+        let timeout = Duration::from_millis(50);
+        let never_returns = |i: u64| {
+            if i == 0 {
+                loop {
+                    std::thread::park();
+                }
+            }
+            i
+        };
+        let engine = Engine { concurrency: 2, timeout: Some(timeout) };
+        assert_eq!(engine.run(vec![0, 1, 2], never_returns), vec![Err(JobError::TimedOut(timeout)), Ok(1), Ok(2)]);
+
+        // The job holds the only slot, the following ones fail instead of waiting for it forever.
+        let engine = Engine { concurrency: 1, timeout: Some(timeout) };
+        assert_eq!(
+            engine.run(vec![1, 0, 2], never_returns),
+            vec![Ok(1), Err(JobError::TimedOut(timeout)), Err(JobError::NoSlot(timeout))]
+        );
+    }
+}
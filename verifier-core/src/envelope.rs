@@ -0,0 +1,265 @@
+// Versioned JSON result printed by the verifiers with `--envelope`, on success as on failure,
+// so that callers do not need to parse stderr.
+
+use std::path::Path;
+use std::time::Instant;
+
+use hyle_contract::readable::BytesFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cache::file_digest;
+use crate::checks::FieldDiff;
+use crate::{catch_panic, ProofOutputs, VerifyError};
+
+pub const ENVELOPE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub version: u32,
+    pub status: Status,
+    pub backend: String,
+    /// Program id as given to the verifier, if the backend takes one.
+    pub program_id: Option<String>,
+    /// Hex SHA-256 digest of the proof file.
+    pub proof_digest: Option<String>,
+    /// Outputs of the proof, printed as without `--envelope`. Only set on success.
+    pub output: Option<Value>,
+    /// Only set on failure.
+    pub error: Option<EnvelopeError>,
+    /// Backend-specific description of the verified proof, such as the kind of a RISC Zero receipt.
+    #[serde(default)]
+    pub proof_info: Option<Value>,
+    /// Hex keys touched by the state diff, when the state transition is checked, see `checks`.
+    #[serde(default)]
+    pub touched_keys: Option<Vec<String>>,
+    /// Whether the outputs were read from the result cache instead of verifying the proof, see `cache`.
+    #[serde(default)]
+    pub cached: bool,
+    pub timings: Timings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeError {
+    /// Kind of the error, see `VerifyError::code`.
+    pub code: String,
+    pub message: String,
+    /// Fields of the outputs which differ from the expected ones, see `VerifyError::OutputMismatch`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diffs: Vec<FieldDiff>,
+}
+
+impl From<&VerifyError> for EnvelopeError {
+    fn from(err: &VerifyError) -> Self {
+        EnvelopeError { code: err.code().to_string(), message: err.message(), diffs: err.diffs().to_vec() }
+    }
+}
+
+/// Time spent in each phase of the verification, in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    /// Reading the proof, the program id and the checks.
+    pub decode_ms: f64,
+    pub verify_ms: f64,
+    pub checks_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Decode,
+    Verify,
+    Checks,
+}
+
+/// Records what the envelope of a verification reports, as it runs.
+pub struct Recorder {
+    backend: String,
+    program_id: Option<String>,
+    proof_digest: Option<String>,
+    started: Instant,
+    proof_info: Option<Value>,
+    touched_keys: Option<Vec<String>>,
+    cached: bool,
+    timings: Timings,
+}
+
+impl Recorder {
+    pub fn new(backend: &str, program_id: Option<&str>, proof: &Path) -> Self {
+        Recorder {
+            backend: backend.to_string(),
+            program_id: program_id.map(str::to_string),
+            proof_digest: file_digest(proof),
+            started: Instant::now(),
+            proof_info: None,
+            touched_keys: None,
+            cached: false,
+            timings: Timings::default(),
+        }
+    }
+
+    /// Runs `f`, adding its duration to the timing of `phase`.
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+        match phase {
+            Phase::Decode => self.timings.decode_ms += elapsed,
+            Phase::Verify => self.timings.verify_ms += elapsed,
+            Phase::Checks => self.timings.checks_ms += elapsed,
+        }
+        result
+    }
+
+    /// Reports a description of the verified proof, see `Envelope::proof_info`.
+    pub fn set_proof_info(&mut self, proof_info: Value) {
+        self.proof_info = Some(proof_info);
+    }
+
+    pub fn proof_info(&self) -> Option<&Value> {
+        self.proof_info.as_ref()
+    }
+
+    /// Reports the keys touched by the state diff, see `Envelope::touched_keys`.
+    pub fn set_touched_keys(&mut self, keys: &[Vec<u8>]) {
+        self.touched_keys = Some(keys.iter().map(hex::encode).collect());
+    }
+
+    /// Marks the outputs as read from the result cache.
+    pub fn set_cached(&mut self) {
+        self.cached = true;
+    }
+
+    /// Envelope of the verification, ending now.
+    pub fn finish(self, result: Result<ProofOutputs, VerifyError>, format: BytesFormat) -> Envelope {
+        let timings = Timings { total_ms: self.started.elapsed().as_secs_f64() * 1000.0, ..self.timings };
+        let (status, output, error) = match result {
+            Ok(outputs) => (Status::Success, Some(outputs.to_json(format)), None),
+            Err(err) => (Status::Failure, None, Some(EnvelopeError::from(&err))),
+        };
+        Envelope {
+            version: ENVELOPE_VERSION,
+            status,
+            backend: self.backend,
+            program_id: self.program_id,
+            proof_digest: self.proof_digest,
+            output,
+            error,
+            proof_info: self.proof_info,
+            touched_keys: self.touched_keys,
+            cached: self.cached,
+            timings,
+        }
+    }
+
+    /// Runs the verification and returns its envelope. Panics are reported as internal errors.
+    pub fn record(
+        mut self,
+        format: BytesFormat,
+        verify: impl FnOnce(&mut Recorder) -> Result<ProofOutputs, VerifyError>,
+    ) -> Envelope {
+        let result = catch_panic(|| verify(&mut self));
+        self.finish(result, format)
+    }
+
+    /// Runs the verification of a verifier binary and prints its outputs, or its envelope if
+    /// `envelope` is set. Then exits with the code of its error, see `VerifyError::exit_code`.
+    pub fn run(
+        mut self,
+        format: BytesFormat,
+        envelope: bool,
+        verify: impl FnOnce(&mut Recorder) -> Result<ProofOutputs, VerifyError>,
+    ) -> ! {
+        let result = catch_panic(|| verify(&mut self));
+        let code = result.as_ref().err().map_or(0, VerifyError::exit_code);
+        if let Err(err) = &result {
+            eprintln!("{}", err);
+        }
+        if let (Some(proof_info), false) = (&self.proof_info, envelope) {
+            eprintln!("proof_info: {}", proof_info);
+        }
+        if let (Some(touched_keys), false) = (&self.touched_keys, envelope) {
+            eprintln!("touched_keys: {}", serde_json::to_string(touched_keys).expect("keys are serializable"));
+        }
+        if envelope {
+            println!("{}", serde_json::to_string(&self.finish(result, format)).expect("envelopes are serializable"));
+        } else if let Ok(outputs) = result {
+            // Outputs to stdout for the caller to read.
+            println!("{}", outputs.to_json(format));
+        }
+        std::process::exit(code)
+    }
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hyle_contract::HyleOutput;
+    use serde_json::json;
+
+    fn new_recorder() -> Recorder {
+        Recorder::new("risc0", Some("abcd"), Path::new("missing.json"))
+    }
+
+    #[test]
+    fn test_envelope() {
+        let output = HyleOutput {
+            version: 2,
+            initial_state: vec![1],
+            next_state: vec![2],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: Value::Null,
+        };
+        let mut recorder = new_recorder();
+        recorder.time(Phase::Verify, || ());
+        let envelope = recorder.finish(Ok(ProofOutputs::Single(output)), BytesFormat::Hex);
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["backend"], "risc0");
+        assert_eq!(json["program_id"], "abcd");
+        assert_eq!(json["proof_digest"], Value::Null);
+        assert_eq!(json["output"]["initial_state"], "0x01");
+        assert_eq!(json["error"], Value::Null);
+        assert_eq!(json["cached"], false);
+        assert_eq!(json["proof_info"], Value::Null);
+        assert_eq!(json["touched_keys"], Value::Null);
+        assert!(envelope.timings.total_ms >= envelope.timings.verify_ms);
+
+        let envelope = new_recorder().finish(Err(VerifyError::InvalidProof("bad seal".to_string())), BytesFormat::Hex);
+        assert_eq!(envelope.status, Status::Failure);
+        assert_eq!(envelope.output, None);
+        assert_eq!(
+            envelope.error,
+            Some(EnvelopeError { code: "invalid_proof".to_string(), message: "bad seal".to_string(), diffs: vec![] })
+        );
+
+        let diff = FieldDiff { output: Some(1), field: "origin".to_string(), expected: json!("alice"), actual: json!("bob") };
+        let envelope = new_recorder().finish(Err(VerifyError::OutputMismatch(vec![diff])), BytesFormat::Hex);
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["error"]["code"], "policy_violation");
+        assert_eq!(json["error"]["message"], r#"output mismatch: output 1: origin: expected "alice", got "bob""#);
+        assert_eq!(json["error"]["diffs"], json!([{ "output": 1, "field": "origin", "expected": "alice", "actual": "bob" }]));
+    }
+
+    #[test]
+    fn test_touched_keys() {
+        let mut recorder = new_recorder();
+        recorder.set_touched_keys(&[b"alice".to_vec(), vec![0xff]]);
+        let envelope = recorder.finish(Err(VerifyError::Decode("bad".to_string())), BytesFormat::Hex);
+        assert_eq!(envelope.touched_keys, Some(vec!["616c696365".to_string(), "ff".to_string()]));
+    }
+}
@@ -0,0 +1,365 @@
+// Shared interface of the Hylé verifiers.
+//
+// Each backend crate implements `Verifier`, so that proofs can be verified in-process, and its
+// binary only parses arguments around it.
+
+use std::fmt;
+
+use hyle_contract::batch::HyleBatchOutput;
+use hyle_contract::readable::{BytesFormat, ReadableOutput};
+use hyle_contract::{encoding, HyleOutput};
+use serde::Serialize;
+use serde_json::Value;
+
+pub mod cache;
+pub mod checks;
+pub mod engine;
+pub mod envelope;
+
+/// Failures shared by all verifiers. Each maps to the process exit code documented on
+/// `exit_code`, so that callers can tell them apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The proof or the program id could not be read.
+    Decode(String),
+    /// The proof does not verify against the program id.
+    InvalidProof(String),
+    /// The proof is valid, but for another program than the one given.
+    KeyMismatch(String),
+    /// The proof verifies, but the output it commits to could not be decoded.
+    OutputDecode(String),
+    /// The output does not pass the checks requested by the caller, see `checks`.
+    PolicyViolation(String),
+    /// The output differs from the values expected by the caller. A policy violation, which
+    /// keeps the fields found so that envelopes can report them.
+    OutputMismatch(Vec<checks::FieldDiff>),
+    /// Unexpected failure of the verifier itself.
+    Internal(String),
+}
+
+impl VerifyError {
+    /// Exit code of the verifier binaries failing with this error:
+    ///
+    /// | code | error             |
+    /// |------|-------------------|
+    /// | 0    | success           |
+    /// | 1    | internal error    |
+    /// | 2    | invalid arguments |
+    /// | 3    | decode error      |
+    /// | 4    | invalid proof     |
+    /// | 5    | key mismatch      |
+    /// | 6    | output decode     |
+    /// | 7    | policy violation  |
+    ///
+    /// Invalid arguments are reported by clap before any verification.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VerifyError::Internal(_) => 1,
+            VerifyError::Decode(_) => 3,
+            VerifyError::InvalidProof(_) => 4,
+            VerifyError::KeyMismatch(_) => 5,
+            VerifyError::OutputDecode(_) => 6,
+            VerifyError::PolicyViolation(_) | VerifyError::OutputMismatch(_) => 7,
+        }
+    }
+
+    /// Stable name of the error kind, as reported in `envelope::Envelope`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerifyError::Decode(_) => "decode",
+            VerifyError::InvalidProof(_) => "invalid_proof",
+            VerifyError::KeyMismatch(_) => "key_mismatch",
+            VerifyError::OutputDecode(_) => "output_decode",
+            VerifyError::PolicyViolation(_) | VerifyError::OutputMismatch(_) => "policy_violation",
+            VerifyError::Internal(_) => "internal",
+        }
+    }
+
+    /// Error of kind `code`, see `code`.
+    pub fn from_code(code: &str, message: String) -> Self {
+        match code {
+            "decode" => VerifyError::Decode(message),
+            "invalid_proof" => VerifyError::InvalidProof(message),
+            "key_mismatch" => VerifyError::KeyMismatch(message),
+            "output_decode" => VerifyError::OutputDecode(message),
+            "policy_violation" => VerifyError::PolicyViolation(message),
+            _ => VerifyError::Internal(message),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            VerifyError::Decode(msg)
+            | VerifyError::InvalidProof(msg)
+            | VerifyError::KeyMismatch(msg)
+            | VerifyError::OutputDecode(msg)
+            | VerifyError::PolicyViolation(msg)
+            | VerifyError::Internal(msg) => msg.clone(),
+            VerifyError::OutputMismatch(diffs) => {
+                let diffs: Vec<String> = diffs.iter().map(ToString::to_string).collect();
+                format!("output mismatch: {}", diffs.join(", "))
+            }
+        }
+    }
+
+    /// Fields found to differ from the expected ones, empty unless the outputs mismatch.
+    pub fn diffs(&self) -> &[checks::FieldDiff] {
+        match self {
+            VerifyError::OutputMismatch(diffs) => diffs,
+            _ => &[],
+        }
+    }
+
+    /// Error of a verifier run in another process, which exited with `code`.
+    pub fn from_exit_code(code: Option<i32>, message: String) -> Self {
+        match code {
+            Some(3) => VerifyError::Decode(message),
+            Some(4) => VerifyError::InvalidProof(message),
+            Some(5) => VerifyError::KeyMismatch(message),
+            Some(6) => VerifyError::OutputDecode(message),
+            Some(7) => VerifyError::PolicyViolation(message),
+            _ => VerifyError::Internal(message),
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Decode(msg) => write!(f, "decode error: {}", msg),
+            VerifyError::InvalidProof(msg) => write!(f, "invalid proof: {}", msg),
+            VerifyError::KeyMismatch(msg) => write!(f, "key mismatch: {}", msg),
+            VerifyError::OutputDecode(msg) => write!(f, "output decode error: {}", msg),
+            VerifyError::PolicyViolation(msg) => write!(f, "policy violation: {}", msg),
+            VerifyError::OutputMismatch(_) => write!(f, "policy violation: {}", self.message()),
+            VerifyError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Outputs committed by a proof: a single transaction, or a batch of them.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ProofOutputs {
+    Single(HyleOutput<Value>),
+    Batch(HyleBatchOutput<Value>),
+}
+
+impl ProofOutputs {
+    /// Outputs of every transaction, in order.
+    pub fn outputs(&self) -> &[HyleOutput<Value>] {
+        match self {
+            ProofOutputs::Single(output) => std::slice::from_ref(output),
+            ProofOutputs::Batch(batch) => &batch.outputs,
+        }
+    }
+
+    /// JSON printed by the verifiers: the output, or the list of outputs of a batch.
+    pub fn to_json(self, format: BytesFormat) -> Value {
+        let json = match self {
+            ProofOutputs::Single(output) => serde_json::to_value(output.readable(format)),
+            ProofOutputs::Batch(batch) => serde_json::to_value(batch.readable(format)),
+        };
+        json.expect("outputs are serializable")
+    }
+
+    /// Parses outputs printed by `to_json`, in any bytes format, as verifiers run in another
+    /// process print them. `program_outputs` default to null when they are not printed.
+    pub fn from_json(json: Value) -> Result<Self, VerifyError> {
+        let parse = |mut output: Value| {
+            if let Some(fields) = output.as_object_mut() {
+                fields.entry("program_outputs").or_insert(Value::Null);
+            }
+            serde_json::from_value::<ReadableOutput<Value>>(output)
+                .map(HyleOutput::from)
+                .map_err(|err| VerifyError::OutputDecode(format!("failed to parse printed output: {}", err)))
+        };
+        match json {
+            Value::Array(outputs) => {
+                let outputs = outputs.into_iter().map(parse).collect::<Result<_, _>>()?;
+                Ok(ProofOutputs::Batch(batch_to_value(HyleBatchOutput::<Value> { outputs })?))
+            }
+            output => Ok(ProofOutputs::Single(parse(output)?)),
+        }
+    }
+}
+
+pub trait Verifier {
+    /// Identifies the program the proof must have been generated for: image id, verification key...
+    type ProgramId;
+    type Proof;
+
+    /// Verifies `proof` against `program_id` and decodes the outputs it commits to.
+    /// The states of a batch are checked to be chained.
+    fn verify_outputs(&self, program_id: &Self::ProgramId, proof: &Self::Proof) -> Result<ProofOutputs, VerifyError>;
+
+    /// Same as `verify_outputs`, for proofs of a single transaction.
+    fn verify(&self, program_id: &Self::ProgramId, proof: &Self::Proof) -> Result<HyleOutput<Value>, VerifyError> {
+        match self.verify_outputs(program_id, proof)? {
+            ProofOutputs::Single(output) => Ok(output),
+            ProofOutputs::Batch(batch) => Err(VerifyError::OutputDecode(format!(
+                "proof holds a batch of {} outputs",
+                batch.outputs.len()
+            ))),
+        }
+    }
+}
+
+/// Message of a panic, from its payload.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(msg), _) => msg.to_string(),
+        (_, Some(msg)) => msg.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+/// Runs `run`, reporting its panics as internal errors.
+pub fn catch_panic<T>(run: impl FnOnce() -> Result<T, VerifyError>) -> Result<T, VerifyError> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(run)).unwrap_or_else(|payload| {
+        Err(VerifyError::Internal(format!("verifier panicked: {}", panic_message(payload.as_ref()))))
+    })
+}
+
+/// Runs the body of a verifier binary, then exits with the code of its error, see
+/// `VerifyError::exit_code`. Panics are reported as internal errors.
+pub fn exit_with(run: impl FnOnce() -> Result<(), VerifyError>) -> ! {
+    match catch_panic(run) {
+        Ok(()) => std::process::exit(0),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(err.exit_code());
+        }
+    }
+}
+
+/// Converts the contract-specific `program_outputs` to JSON.
+pub fn to_value<T: Serialize>(output: HyleOutput<T>) -> Result<HyleOutput<Value>, VerifyError> {
+    let program_outputs =
+        serde_json::to_value(output.program_outputs).map_err(|err| VerifyError::OutputDecode(err.to_string()))?;
+    Ok(HyleOutput {
+        version: output.version,
+        initial_state: output.initial_state,
+        next_state: output.next_state,
+        origin: output.origin,
+        caller: output.caller,
+        block_number: output.block_number,
+        block_time: output.block_time,
+        tx_hash: output.tx_hash,
+        events: output.events,
+        program_outputs,
+    })
+}
+
+/// Same as `to_value`, for every output of a batch. Also checks that their states are chained.
+pub fn batch_to_value<T: Serialize>(batch: HyleBatchOutput<T>) -> Result<HyleBatchOutput<Value>, VerifyError> {
+    batch.check_chain().map_err(|err| VerifyError::OutputDecode(format!("invalid batch: {}", err)))?;
+    let outputs = batch.outputs.into_iter().map(to_value).collect::<Result<_, _>>()?;
+    Ok(HyleBatchOutput { outputs })
+}
+
+/// Decodes a canonically encoded output or batch, see `hyle_contract::encoding`.
+pub fn decode_canonical(bytes: &[u8]) -> Result<ProofOutputs, VerifyError> {
+    let decode_error = |err: encoding::DecodeError| VerifyError::OutputDecode(err.to_string());
+    if encoding::is_canonical_batch(bytes) {
+        let batch = encoding::decode_batch(bytes).map_err(decode_error)?;
+        Ok(ProofOutputs::Batch(batch_to_value(batch)?))
+    } else {
+        let output = encoding::decode(bytes).map_err(decode_error)?;
+        Ok(ProofOutputs::Single(to_value(output)?))
+    }
+}
+
+/// Decodes a canonically encoded output or batch given out of band, for proofs which only
+/// commit to its `commitment`.
+pub fn decode_committed(commitment: &[u8], bytes: &[u8]) -> Result<ProofOutputs, VerifyError> {
+    let decode_error = |err: encoding::DecodeError| VerifyError::OutputDecode(err.to_string());
+    let expected = if encoding::is_canonical_batch(bytes) {
+        encoding::decode_batch(bytes).map_err(decode_error)?.commitment()
+    } else {
+        encoding::decode(bytes).map_err(decode_error)?.commitment()
+    };
+    let expected = expected.map_err(|err| VerifyError::OutputDecode(err.to_string()))?;
+    if commitment != expected {
+        return Err(VerifyError::OutputDecode("output does not match the commitment in the proof".to_string()));
+    }
+    decode_canonical(bytes)
+}
+
+// TESTS
+// ================================================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output(initial_state: u8, next_state: u8) -> HyleOutput<Vec<u8>> {
+        HyleOutput {
+            version: 2,
+            initial_state: vec![initial_state],
+            next_state: vec![next_state],
+            origin: "alice".to_string(),
+            caller: "bob".to_string(),
+            block_number: 42,
+            block_time: 0,
+            tx_hash: vec![],
+            events: vec![],
+            program_outputs: vec![7],
+        }
+    }
+
+    #[test]
+    fn test_decode_committed() {
+        let bytes = encoding::encode(&output(1, 2)).unwrap();
+        let commitment = output(1, 2).commitment().unwrap();
+        let outputs = decode_committed(&commitment, &bytes).unwrap();
+        assert_eq!(outputs.outputs()[0].program_outputs, serde_json::json!([7]));
+        assert!(matches!(decode_committed(&[0; 32], &bytes), Err(VerifyError::OutputDecode(_))));
+
+        let batch = HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3)] };
+        let outputs = decode_committed(&batch.commitment().unwrap(), &encoding::encode_batch(&batch).unwrap()).unwrap();
+        assert_eq!(outputs.outputs().len(), 2);
+        assert!(outputs.to_json(BytesFormat::Hex).is_array());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let outputs = ProofOutputs::Single(to_value(output(1, 2)).unwrap());
+        for format in [BytesFormat::Array, BytesFormat::Hex, BytesFormat::Base64] {
+            assert_eq!(ProofOutputs::from_json(outputs.clone().to_json(format)), Ok(outputs.clone()));
+        }
+        let mut json = outputs.to_json(BytesFormat::Hex);
+        json.as_object_mut().unwrap().remove("program_outputs");
+        assert_eq!(ProofOutputs::from_json(json).unwrap().outputs()[0].program_outputs, Value::Null);
+
+        let batch = ProofOutputs::Batch(batch_to_value(HyleBatchOutput { outputs: vec![output(1, 2), output(2, 3)] }).unwrap());
+        assert_eq!(ProofOutputs::from_json(batch.clone().to_json(BytesFormat::Array)), Ok(batch));
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let errors = [
+            VerifyError::Decode(String::new()),
+            VerifyError::InvalidProof(String::new()),
+            VerifyError::KeyMismatch(String::new()),
+            VerifyError::OutputDecode(String::new()),
+            VerifyError::PolicyViolation(String::new()),
+            VerifyError::Internal(String::new()),
+        ];
+        for err in errors {
+            assert_eq!(VerifyError::from_exit_code(Some(err.exit_code()), String::new()), err);
+            assert_eq!(VerifyError::from_code(err.code(), String::new()), err);
+        }
+        assert_eq!(VerifyError::from_exit_code(None, String::new()), VerifyError::Internal(String::new()));
+        // Mismatches are reported as policy violations, with their diffs.
+        let mismatch = VerifyError::OutputMismatch(vec![]);
+        assert_eq!((mismatch.exit_code(), mismatch.code()), (7, "policy_violation"));
+    }
+
+    #[test]
+    fn test_broken_batch() {
+        let batch = HyleBatchOutput { outputs: vec![output(1, 2), output(3, 4)] };
+        assert!(matches!(decode_canonical(&encoding::encode_batch(&batch).unwrap()), Err(VerifyError::OutputDecode(_))));
+    }
+}